@@ -7,20 +7,156 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-///
-#[derive(Debug, Copy, Clone)]
+/// An error that occurred while loading a library.
+#[derive(Debug, Clone)]
 pub enum Error {
     /// Library could not be found.
-    NotInstalled,
+    NotInstalled {
+        /// Name or path of the library that was requested.
+        library: String,
+        /// Why the dynamic loader failed to load it.
+        reason: LoaderError,
+    },
     /// Function or global static doesn't exist in this library.
-    DoesntExist(&'static str),
+    DoesntExist {
+        /// Name or path of the library that was searched.
+        library: String,
+        /// Name of the symbol that was requested.
+        symbol: String,
+        /// Why the dynamic loader failed to find it.
+        reason: LoaderError,
+    },
+    /// Function or global static exists, but its value is NULL.
+    NullSymbol {
+        /// Name or path of the library that was searched.
+        library: String,
+        /// Name of the symbol that was requested.
+        symbol: String,
+    },
+    /// Information about a loaded library couldn't be queried.
+    InfoUnavailable {
+        /// Name or path of the library that was queried.
+        library: String,
+        /// Why the dynamic loader failed to answer.
+        reason: LoaderError,
+    },
+    /// Library's bytes couldn't be written to a file to load them from.
+    TempFile {
+        /// Why writing the file failed.
+        error: std::sync::Arc<std::io::Error>,
+    },
+    /// Library file couldn't be read.
+    ReadFailed {
+        /// Path of the library.
+        library: String,
+        /// Why reading the file failed.
+        error: std::sync::Arc<std::io::Error>,
+    },
+    /// None of the candidate libraries could be loaded.
+    NoneInstalled {
+        /// Why each of the candidates failed to load, in order.
+        attempts: Vec<Error>,
+    },
+    /// None of the candidate names for a symbol exist in the library.
+    NoneExist {
+        /// Why each of the names couldn't be found, in order.
+        attempts: Vec<Error>,
+    },
+    /// Name of a library or symbol contains a NUL character.
+    NulCharacter {
+        /// The name, with the NUL character.
+        name: String,
+        /// Where the NUL character was found.
+        error: std::ffi::NulError,
+    },
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Error::NotInstalled => write!(f, "Not Installed"),
-            Error::DoesntExist(details) => write!(f, "Symbol \"{}\" doesn't exist", details),
+            Error::NotInstalled { library, .. } => {
+                write!(f, "Library \"{}\" not installed", library)
+            }
+            Error::DoesntExist {
+                library, symbol, ..
+            } => write!(
+                f,
+                "Symbol \"{}\" doesn't exist in \"{}\"",
+                symbol, library
+            ),
+            Error::NullSymbol { library, symbol } => {
+                write!(f, "Symbol \"{}\" is NULL in \"{}\"", symbol, library)
+            }
+            Error::InfoUnavailable { library, .. } => {
+                write!(f, "Info unavailable for library \"{}\"", library)
+            }
+            Error::TempFile { .. } => {
+                write!(f, "Couldn't write library to a temporary file")
+            }
+            Error::ReadFailed { library, .. } => {
+                write!(f, "Couldn't read library \"{}\"", library)
+            }
+            Error::NoneInstalled { attempts } => {
+                write!(f, "None of the libraries are installed:")?;
+                for attempt in attempts {
+                    write!(f, " ({})", attempt)?;
+                }
+                Ok(())
+            }
+            Error::NoneExist { attempts } => {
+                write!(f, "None of the symbols exist:")?;
+                for attempt in attempts {
+                    write!(f, " ({})", attempt)?;
+                }
+                Ok(())
+            }
+            Error::NulCharacter { name, .. } => {
+                write!(f, "Name \"{}\" contains a NUL character", name)
+            }
         }
     }
 }
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NotInstalled { reason, .. }
+            | Error::DoesntExist { reason, .. }
+            | Error::InfoUnavailable { reason, .. } => Some(reason),
+            Error::NoneInstalled { attempts }
+            | Error::NoneExist { attempts } => match attempts.first() {
+                Some(attempt) => Some(attempt),
+                None => None,
+            },
+            Error::TempFile { error } | Error::ReadFailed { error, .. } => {
+                Some(&**error)
+            }
+            Error::NulCharacter { error, .. } => Some(error),
+            Error::NullSymbol { .. } => None,
+        }
+    }
+}
+
+/// Diagnostic message from the operating system's dynamic loader (what
+/// `dlerror()` returned).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoaderError(String);
+
+impl LoaderError {
+    pub(crate) fn new(message: String) -> Self {
+        LoaderError(message)
+    }
+
+    /// Get the message from the dynamic loader.
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for LoaderError {}
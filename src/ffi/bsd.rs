@@ -7,4 +7,29 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::os::raw::c_int;
+
+const RTLD_LAZY: c_int = 1;
+const RTLD_NOW: c_int = 2;
+const RTLD_GLOBAL: c_int = 0x100;
+#[cfg(target_os = "netbsd")]
+const RTLD_LOCAL: c_int = 0x200;
+#[cfg(not(target_os = "netbsd"))]
+const RTLD_LOCAL: c_int = 0;
+#[cfg(target_os = "openbsd")]
+const RTLD_NODELETE: c_int = 0x400;
+#[cfg(not(target_os = "openbsd"))]
+const RTLD_NODELETE: c_int = 0x1000;
+#[cfg(target_os = "openbsd")]
+const RTLD_NOLOAD: c_int = 0x800;
+#[cfg(not(target_os = "openbsd"))]
+const RTLD_NOLOAD: c_int = 0x2000;
+#[cfg(target_os = "freebsd")]
+const RTLD_DEEPBIND: c_int = 0x40000;
+#[cfg(not(target_os = "freebsd"))]
+const RTLD_DEEPBIND: c_int = 0; // Only supported on FreeBSD
+
+const RTLD_DEFAULT: usize = usize::MAX - 1; // -2
+const RTLD_NEXT: usize = usize::MAX; // -1
+
 include!("unix.rs");
@@ -7,4 +7,234 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::os::raw::c_int;
+
+const RTLD_LAZY: c_int = 0x00001;
+const RTLD_NOW: c_int = 0x00002;
+const RTLD_NOLOAD: c_int = 0x00004;
+#[cfg(target_env = "gnu")]
+const RTLD_DEEPBIND: c_int = 0x00008;
+#[cfg(not(target_env = "gnu"))]
+const RTLD_DEEPBIND: c_int = 0; // Not supported by musl
+const RTLD_GLOBAL: c_int = 0x00100;
+const RTLD_LOCAL: c_int = 0;
+const RTLD_NODELETE: c_int = 0x01000;
+
+const RTLD_DEFAULT: usize = 0;
+const RTLD_NEXT: usize = usize::MAX; // -1
+
 include!("unix.rs");
+
+/// Link-map namespace ID.
+#[cfg(target_env = "gnu")]
+pub(super) type Lmid = std::os::raw::c_long;
+
+/// The default namespace, that the program itself is loaded into.
+#[cfg(target_env = "gnu")]
+pub(super) const LM_ID_BASE: Lmid = 0;
+
+#[cfg(target_env = "gnu")]
+impl DlApi {
+    /// Load a Dynamic Library API into a link-map namespace, or a new one if
+    /// `None`.
+    pub(super) fn new_in(
+        namespace: Option<Lmid>,
+        filename: &CStr,
+        options: &OpenOptions,
+    ) -> Result<Self, String> {
+        const LM_ID_NEWLM: Lmid = -1;
+        extern "C" {
+            fn dlmopen(
+                lmid: Lmid,
+                name: *const c_char,
+                flags: c_int,
+            ) -> *mut DlObj;
+        }
+        let lmid = namespace.unwrap_or(LM_ID_NEWLM);
+        let lock = lock_dlerror();
+        let dlobj = unsafe { dlmopen(lmid, filename.as_ptr(), flags(options)) };
+        Self::opened(dlobj, &lock)
+    }
+
+    /// Get a specific version of a function or global from the library.
+    pub(super) fn get_versioned(
+        &self,
+        symbol: &CStr,
+        version: &CStr,
+    ) -> Result<Option<NonNull<c_void>>, String> {
+        extern "C" {
+            fn dlvsym(
+                dlobj: *mut DlObj,
+                symbol: *const c_char,
+                version: *const c_char,
+            ) -> *mut c_void;
+        }
+        let lock = lock_dlerror();
+        let _ = dlerror(&lock);
+        let pointer =
+            unsafe { dlvsym(self.dlobj, symbol.as_ptr(), version.as_ptr()) };
+        Self::found(pointer, &lock)
+    }
+
+    /// Query the dynamic loader about the library.
+    fn dlinfo<T>(&self, request: c_int, info: *mut T) -> Result<(), String> {
+        extern "C" {
+            fn dlinfo(
+                dlobj: *mut DlObj,
+                request: c_int,
+                info: *mut c_void,
+            ) -> c_int;
+        }
+        if !self.close {
+            return Err("Not a library handle".to_string());
+        }
+        let lock = lock_dlerror();
+        if unsafe { dlinfo(self.dlobj, request, info.cast()) } == 0 {
+            Ok(())
+        } else {
+            Err(dlerror(&lock)
+                .unwrap_or_else(|| "Failed to query library".to_string()))
+        }
+    }
+
+    /// Get the link-map namespace that the library was loaded into.
+    pub(super) fn namespace(&self) -> Option<Lmid> {
+        const RTLD_DI_LMID: c_int = 1;
+        let mut lmid: Lmid = 0;
+        self.dlinfo(RTLD_DI_LMID, &mut lmid).ok()?;
+        Some(lmid)
+    }
+
+    /// Get the base address and path of the library from its link map.
+    pub(super) fn link_map(&self) -> Result<(usize, PathBuf), String> {
+        const RTLD_DI_LINKMAP: c_int = 2;
+        let mut link_map: *const LinkMap = ptr::null();
+        self.dlinfo(RTLD_DI_LINKMAP, &mut link_map)?;
+        let link_map = unsafe { &*link_map };
+        let path = if link_map.name.is_null() {
+            PathBuf::new()
+        } else {
+            let path = unsafe { CStr::from_ptr(link_map.name) };
+            PathBuf::from(OsStr::from_bytes(path.to_bytes()))
+        };
+        Ok((link_map.addr, path))
+    }
+
+    /// Get the directory that the library was loaded from (`$ORIGIN`).
+    pub(super) fn origin(&self) -> Result<PathBuf, String> {
+        const RTLD_DI_ORIGIN: c_int = 6;
+        const PATH_MAX: usize = 4096;
+        let mut origin: [c_char; PATH_MAX] = [0; PATH_MAX];
+        self.dlinfo(RTLD_DI_ORIGIN, origin.as_mut_ptr())?;
+        let origin = unsafe { CStr::from_ptr(origin.as_ptr()) };
+        Ok(PathBuf::from(OsStr::from_bytes(origin.to_bytes())))
+    }
+
+    /// Get the directories that are searched for the library's dependencies.
+    pub(super) fn search_path(&self) -> Result<Vec<PathBuf>, String> {
+        const RTLD_DI_SERINFO: c_int = 4;
+        const RTLD_DI_SERINFOSIZE: c_int = 5;
+        let mut header = DlSerinfo {
+            size: 0,
+            count: 0,
+            paths: [DlSerpath {
+                name: ptr::null(),
+                flags: 0,
+            }],
+        };
+        self.dlinfo(RTLD_DI_SERINFOSIZE, &mut header)?;
+        // Pointer-aligned buffer, with room for the path strings at the end.
+        let words = header.size.div_ceil(size_of::<usize>());
+        let mut buffer = vec![0usize; words.max(1)];
+        let info: *mut DlSerinfo = buffer.as_mut_ptr().cast();
+        unsafe {
+            (*info).size = header.size;
+            (*info).count = header.count;
+        }
+        self.dlinfo(RTLD_DI_SERINFO, info)?;
+        let paths = unsafe {
+            std::slice::from_raw_parts(
+                ptr::addr_of!((*info).paths).cast::<DlSerpath>(),
+                (*info).count as usize,
+            )
+        };
+        Ok(paths
+            .iter()
+            .map(|path| {
+                let path = unsafe { CStr::from_ptr(path.name) };
+                PathBuf::from(OsStr::from_bytes(path.to_bytes()))
+            })
+            .collect())
+    }
+}
+
+/// Start of glibc's `struct link_map`.
+#[cfg(target_env = "gnu")]
+#[repr(C)]
+struct LinkMap {
+    addr: usize,
+    name: *const c_char,
+}
+
+/// Search path directory for `RTLD_DI_SERINFO`.
+#[cfg(target_env = "gnu")]
+#[allow(dead_code)] // Must match the C struct
+#[repr(C)]
+struct DlSerpath {
+    name: *const c_char,
+    flags: std::os::raw::c_uint,
+}
+
+/// Search path for `RTLD_DI_SERINFO`.
+#[cfg(target_env = "gnu")]
+#[repr(C)]
+struct DlSerinfo {
+    size: usize,
+    count: std::os::raw::c_uint,
+    paths: [DlSerpath; 1],
+}
+
+/// ELF symbol table entry.
+#[cfg(all(target_env = "gnu", target_pointer_width = "64"))]
+#[allow(dead_code)] // Must match the C struct
+#[repr(C)]
+struct ElfSym {
+    name: u32,
+    info: u8,
+    other: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+/// ELF symbol table entry.
+#[cfg(all(target_env = "gnu", target_pointer_width = "32"))]
+#[allow(dead_code)] // Must match the C struct
+#[repr(C)]
+struct ElfSym {
+    name: u32,
+    value: u32,
+    size: u32,
+    info: u8,
+    other: u8,
+    shndx: u16,
+}
+
+/// Fill in `info` for an address, returning non-zero on success, and the size
+/// of the symbol.
+#[cfg(target_env = "gnu")]
+fn dladdr(address: *const c_void, info: &mut DlInfo) -> (c_int, Option<usize>) {
+    const RTLD_DL_SYMENT: c_int = 1;
+    extern "C" {
+        fn dladdr1(
+            address: *const c_void,
+            info: *mut DlInfo,
+            extra_info: *mut *const ElfSym,
+            flags: c_int,
+        ) -> c_int;
+    }
+    let mut symbol: *const ElfSym = ptr::null();
+    let found = unsafe { dladdr1(address, info, &mut symbol, RTLD_DL_SYMENT) };
+    let size = unsafe { symbol.as_ref() }.map(|symbol| symbol.size as usize);
+    (found, size)
+}
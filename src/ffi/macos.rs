@@ -7,4 +7,17 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::os::raw::c_int;
+
+const RTLD_LAZY: c_int = 0x1;
+const RTLD_NOW: c_int = 0x2;
+const RTLD_LOCAL: c_int = 0x4;
+const RTLD_GLOBAL: c_int = 0x8;
+const RTLD_NOLOAD: c_int = 0x10;
+const RTLD_NODELETE: c_int = 0x80;
+const RTLD_DEEPBIND: c_int = 0; // Not supported by dyld
+
+const RTLD_DEFAULT: usize = usize::MAX - 1; // -2
+const RTLD_NEXT: usize = usize::MAX; // -1
+
 include!("unix.rs");
@@ -7,4 +7,17 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::os::raw::c_int;
+
+const RTLD_LAZY: c_int = 0x0001;
+const RTLD_NOW: c_int = 0x0002;
+const RTLD_GLOBAL: c_int = 0x0100;
+const RTLD_LOCAL: c_int = 0x0000;
+const RTLD_NOLOAD: c_int = 0; // Not supported by relibc
+const RTLD_NODELETE: c_int = 0; // Not supported by relibc
+const RTLD_DEEPBIND: c_int = 0; // Not supported by relibc
+
+const RTLD_DEFAULT: usize = 0;
+const RTLD_NEXT: usize = usize::MAX; // -1
+
 include!("unix.rs");
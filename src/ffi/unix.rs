@@ -7,33 +7,513 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::ffi::CStr;
-use std::os::raw::{c_void, c_char, c_int};
-use std::ptr::NonNull;
+use std::ffi::{CStr, CString, NulError, OsStr};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::raw::{c_void, c_char};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr::{self, NonNull};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "redox"
+)))]
+use crate::manual::{ProgramHeader, SharedObject};
+use crate::manual::{OpenOptions, SymbolInfo};
 
 #[repr(transparent)]
 struct DlObj(c_void);
 
+// `dlerror()` state isn't guaranteed to be thread-local, so it must be locked
+// from the call that might fail until the error message is read.
+static DLERROR_MUTEX: Mutex<()> = Mutex::new(());
+
+fn lock_dlerror() -> MutexGuard<'static, ()> {
+    DLERROR_MUTEX.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Take the last error message from the dynamic loader (must be locked).
+fn dlerror(_lock: &MutexGuard<'static, ()>) -> Option<String> {
+    extern "C" {
+        fn dlerror() -> *mut c_char;
+    }
+    let message = unsafe { dlerror() };
+    if message.is_null() {
+        None
+    } else {
+        let message = unsafe { CStr::from_ptr(message) };
+        Some(message.to_string_lossy().into_owned())
+    }
+}
+
 /// Dynamically loaded library API.
 #[derive(Debug)]
-pub(super) struct DlApi(NonNull<DlObj>);
+pub(super) struct DlApi {
+    dlobj: *mut DlObj,
+    // Pseudo-handles weren't returned by `dlopen()`, so aren't closed.
+    close: bool,
+}
+
+// The handle can be used from any thread; `dlerror()` is guarded separately.
+unsafe impl Send for DlApi {}
+unsafe impl Sync for DlApi {}
 
 impl DlApi {
     /// Load a Dynamic Library API.
-    pub(super) fn new(filename: &CStr) -> Option<Self> {
+    pub(super) fn new(
+        filename: &CStr,
+        options: &OpenOptions,
+    ) -> Result<Self, String> {
         extern "C" {
             fn dlopen(name: *const c_char, flags: c_int) -> *mut DlObj;
         }
-        Some(DlApi(unsafe {
-            NonNull::new(dlopen(filename.as_ptr(), 0x00002 /*NOW*/))?
-        }))
+        let lock = lock_dlerror();
+        let dlobj = unsafe { dlopen(filename.as_ptr(), flags(options)) };
+        Self::opened(dlobj, &lock)
+    }
+
+    /// Get a handle to a library only if it's already loaded.
+    pub(super) fn get_if_loaded(filename: &CStr) -> Option<Self> {
+        if RTLD_NOLOAD == 0 {
+            // Without `RTLD_NOLOAD`, `dlopen()` would load the library.
+            return None;
+        }
+        let mut options = OpenOptions::default();
+        options.noload = true;
+        Self::new(filename, &options).ok()
     }
 
-    /// Get a function or global from the library.
-    pub(super) fn get(&self, symbol: &CStr) -> Option<NonNull<c_void>> {
+    /// Open the main program.
+    pub(super) fn process() -> Result<Self, String> {
+        extern "C" {
+            fn dlopen(name: *const c_char, flags: c_int) -> *mut DlObj;
+        }
+        let lock = lock_dlerror();
+        let flags = flags(&OpenOptions::default());
+        let dlobj = unsafe { dlopen(ptr::null(), flags) };
+        Self::opened(dlobj, &lock)
+    }
+
+    /// Pseudo-handle that searches the global scope (`RTLD_DEFAULT`).
+    pub(super) fn default_scope() -> Self {
+        DlApi {
+            dlobj: RTLD_DEFAULT as *mut DlObj,
+            close: false,
+        }
+    }
+
+    /// Pseudo-handle that searches the objects loaded after this one
+    /// (`RTLD_NEXT`).
+    pub(super) fn next_scope() -> Self {
+        DlApi {
+            dlobj: RTLD_NEXT as *mut DlObj,
+            close: false,
+        }
+    }
+
+    /// Wrap a handle returned from `dlopen()` (must be locked).
+    fn opened(
+        dlobj: *mut DlObj,
+        lock: &MutexGuard<'static, ()>,
+    ) -> Result<Self, String> {
+        if dlobj.is_null() {
+            Err(dlerror(lock)
+                .unwrap_or_else(|| "Failed to load library".to_string()))
+        } else {
+            Ok(DlApi { dlobj, close: true })
+        }
+    }
+
+    /// Get a function or global from the library, or `None` if the symbol's
+    /// value is NULL.
+    pub(super) fn get(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<NonNull<c_void>>, String> {
         extern "C" {
             fn dlsym(dlobj: *mut DlObj, symbol: *const c_char) -> *mut c_void;
         }
-        unsafe { NonNull::new(dlsym(self.0.as_ptr(), symbol.as_ptr())) }
+        let lock = lock_dlerror();
+        // Clear any previous error, so that it's not mistaken for this one.
+        let _ = dlerror(&lock);
+        let pointer = unsafe { dlsym(self.dlobj, symbol.as_ptr()) };
+        Self::found(pointer, &lock)
+    }
+
+    /// Get the address of a thread-local variable for the calling thread.
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    pub(super) fn get_tls(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<NonNull<c_void>>, String> {
+        // `dlsym()` resolves thread-local symbols for the calling thread.
+        self.get(symbol)
+    }
+
+    /// Get the address of a thread-local variable for the calling thread.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub(super) fn get_tls(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<NonNull<c_void>>, String> {
+        // `dlsym()` returns the variable's descriptor, which has a function to
+        // get the address for the calling thread.
+        #[repr(C)]
+        struct TlvDescriptor {
+            thunk: unsafe extern "C" fn(*mut TlvDescriptor) -> *mut c_void,
+            key: usize,
+            offset: usize,
+        }
+        let descriptor = match self.get(symbol)? {
+            Some(descriptor) => descriptor.cast::<TlvDescriptor>().as_ptr(),
+            None => return Ok(None),
+        };
+        Ok(NonNull::new(unsafe { ((*descriptor).thunk)(descriptor) }))
+    }
+
+    /// Check whether two handles are for the same library.
+    pub(super) fn is_same(&self, other: &Self) -> bool {
+        self.dlobj == other.dlobj
+    }
+
+    /// Check a pointer returned from `dlsym()` (must be locked, and `dlerror()`
+    /// must have been cleared before the call).  NULL is only an error if
+    /// `dlerror()` reports one, otherwise it's the symbol's value.
+    fn found(
+        pointer: *mut c_void,
+        lock: &MutexGuard<'static, ()>,
+    ) -> Result<Option<NonNull<c_void>>, String> {
+        match NonNull::new(pointer) {
+            Some(pointer) => Ok(Some(pointer)),
+            None => match dlerror(lock) {
+                Some(message) => Err(message),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+impl Drop for DlApi {
+    fn drop(&mut self) {
+        extern "C" {
+            fn dlclose(dlobj: *mut DlObj) -> c_int;
+        }
+        if self.close {
+            unsafe {
+                dlclose(self.dlobj);
+            }
+        }
+    }
+}
+
+/// Information about the library and symbol containing an address.
+#[repr(C)]
+struct DlInfo {
+    fname: *const c_char,
+    fbase: *mut c_void,
+    sname: *const c_char,
+    saddr: *mut c_void,
+}
+
+/// Find the library and symbol containing an address.
+pub(super) fn symbol_info(address: *const c_void) -> Option<SymbolInfo> {
+    let mut info = DlInfo {
+        fname: ptr::null(),
+        fbase: ptr::null_mut(),
+        sname: ptr::null(),
+        saddr: ptr::null_mut(),
+    };
+    let (found, symbol_size) = dladdr(address, &mut info);
+    if found == 0 || info.fname.is_null() {
+        return None;
+    }
+    let path = unsafe { CStr::from_ptr(info.fname) };
+    let symbol = if info.sname.is_null() {
+        None
+    } else {
+        let symbol = unsafe { CStr::from_ptr(info.sname) };
+        Some(symbol.to_string_lossy().into_owned())
+    };
+    Some(SymbolInfo {
+        address: address as usize,
+        path: PathBuf::from(OsStr::from_bytes(path.to_bytes())),
+        base: info.fbase as usize,
+        symbol,
+        symbol_address: NonNull::new(info.saddr).map(|a| a.as_ptr() as usize),
+        symbol_size,
+    })
+}
+
+/// Fill in `info` for an address, returning non-zero on success.
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn dladdr(address: *const c_void, info: &mut DlInfo) -> (c_int, Option<usize>) {
+    extern "C" {
+        fn dladdr(address: *const c_void, info: *mut DlInfo) -> c_int;
+    }
+    (unsafe { dladdr(address, info) }, None)
+}
+
+/// ELF program header (`ElfW(Phdr)`).
+#[cfg(all(
+    target_pointer_width = "64",
+    not(any(target_os = "macos", target_os = "ios", target_os = "redox"))
+))]
+#[repr(C)]
+struct Phdr {
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+/// ELF program header (`ElfW(Phdr)`).
+#[cfg(all(
+    target_pointer_width = "32",
+    not(any(target_os = "macos", target_os = "ios", target_os = "redox"))
+))]
+#[repr(C)]
+struct Phdr {
+    kind: u32,
+    offset: u32,
+    vaddr: u32,
+    paddr: u32,
+    filesz: u32,
+    memsz: u32,
+    flags: u32,
+    align: u32,
+}
+
+/// Information about a loaded object for `dl_iterate_phdr()`.  Some systems
+/// only pass the fields up to `phnum`.
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "redox")))]
+#[allow(dead_code)] // Must match the C struct
+#[repr(C)]
+struct DlPhdrInfo {
+    addr: usize,
+    name: *const c_char,
+    phdr: *const Phdr,
+    phnum: u16,
+    adds: u64,
+    subs: u64,
+    tls_modid: usize,
+    tls_data: *mut c_void,
+}
+
+/// List the objects loaded in the process, with `dl_iterate_phdr()`.
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "redox")))]
+pub(super) fn loaded_libraries() -> Vec<SharedObject> {
+    type Callback =
+        unsafe extern "C" fn(*mut DlPhdrInfo, usize, *mut c_void) -> c_int;
+    extern "C" {
+        fn dl_iterate_phdr(callback: Callback, data: *mut c_void) -> c_int;
+    }
+    unsafe extern "C" fn callback(
+        info: *mut DlPhdrInfo,
+        size: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        let objects = &mut *data.cast::<Vec<SharedObject>>();
+        let path = if (*info).name.is_null() {
+            PathBuf::new()
+        } else {
+            let path = CStr::from_ptr((*info).name);
+            PathBuf::from(OsStr::from_bytes(path.to_bytes()))
+        };
+        let program_headers = if (*info).phdr.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts((*info).phdr, (*info).phnum.into())
+        };
+        let tls_module = if size >= size_of::<DlPhdrInfo>() {
+            Some((*info).tls_modid).filter(|&id| id != 0)
+        } else {
+            None
+        };
+        objects.push(SharedObject {
+            path,
+            base: (*info).addr,
+            program_headers: program_headers
+                .iter()
+                .map(|phdr| ProgramHeader {
+                    kind: phdr.kind,
+                    flags: phdr.flags,
+                    offset: phdr.offset as usize,
+                    virtual_address: phdr.vaddr as usize,
+                    file_size: phdr.filesz as usize,
+                    memory_size: phdr.memsz as usize,
+                    alignment: phdr.align as usize,
+                })
+                .collect(),
+            tls_module,
+        });
+        0
+    }
+    let mut objects = Vec::new();
+    let data: *mut Vec<SharedObject> = &mut objects;
+    unsafe {
+        dl_iterate_phdr(callback, data.cast());
+    }
+    objects
+}
+
+/// A copy of a library's bytes that `dlopen()` can load, which is closed (and
+/// removed, if it's a temporary file) when dropped.
+#[derive(Debug)]
+pub(super) struct LibraryFile {
+    file: File,
+    path: CString,
+    temporary: bool,
+}
+
+impl LibraryFile {
+    /// Write the bytes to an anonymous memory file, or a temporary file if
+    /// that's not supported.
+    pub(super) fn new(bytes: &[u8]) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(library_file) = Self::memfd(bytes)? {
+                return Ok(library_file);
+            }
+        }
+        Self::temporary(bytes)
+    }
+
+    /// Write the bytes to a memory file, opened through `/proc/self/fd`.
+    #[cfg(target_os = "linux")]
+    fn memfd(bytes: &[u8]) -> io::Result<Option<Self>> {
+        use std::os::unix::io::FromRawFd;
+
+        const MFD_CLOEXEC: std::os::raw::c_uint = 1;
+        extern "C" {
+            fn memfd_create(
+                name: *const c_char,
+                flags: std::os::raw::c_uint,
+            ) -> c_int;
+        }
+        let name = b"dl_api\0".as_ptr().cast();
+        let fd = unsafe { memfd_create(name, MFD_CLOEXEC) };
+        if fd < 0 {
+            // Not supported by this kernel, so fall back to a temporary file.
+            return Ok(None);
+        }
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        file.write_all(bytes)?;
+        let path = CString::new(format!("/proc/self/fd/{}", fd))?;
+        if !Path::new(OsStr::from_bytes(path.to_bytes())).exists() {
+            // `/proc` isn't mounted.
+            return Ok(None);
+        }
+        Ok(Some(LibraryFile {
+            file,
+            path,
+            temporary: false,
+        }))
+    }
+
+    /// Write the bytes to a new file in the temporary directory, that only
+    /// the current user can access.
+    fn temporary(bytes: &[u8]) -> io::Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "dl_api-{}-{}.so",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o700)
+                .open(&path);
+            let file = match file {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            // Removed when dropped, even if writing fails.
+            let mut library_file = LibraryFile {
+                file,
+                path: path_to_cstring(&path)?,
+                temporary: true,
+            };
+            library_file.file.write_all(bytes)?;
+            return Ok(library_file);
+        }
+    }
+
+    /// Move a memory file to a higher file descriptor, which gives it a new
+    /// path.
+    pub(super) fn renumber(&mut self) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::io::{AsRawFd, FromRawFd};
+
+            const F_DUPFD_CLOEXEC: c_int = 1030;
+            extern "C" {
+                fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+            }
+            if !self.temporary {
+                let old = self.file.as_raw_fd();
+                let fd = unsafe { fcntl(old, F_DUPFD_CLOEXEC, old + 1) };
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let path = CString::new(format!("/proc/self/fd/{}", fd))?;
+                self.file = unsafe { File::from_raw_fd(fd) };
+                self.path = path;
+                return Ok(());
+            }
+        }
+        Err(io::Error::other("Only memory files can be renumbered"))
+    }
+
+    /// Get the path to pass to `dlopen()`.
+    pub(super) fn path(&self) -> &CStr {
+        &self.path
+    }
+
+    /// Check whether this is a memory file, rather than a temporary file.
+    pub(super) fn is_memory(&self) -> bool {
+        !self.temporary
+    }
+}
+
+impl Drop for LibraryFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let path = Path::new(OsStr::from_bytes(self.path.to_bytes()));
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Convert a path to a NUL-terminated string for `dlopen()`.
+pub(super) fn path_to_cstring(path: &Path) -> Result<CString, NulError> {
+    CString::new(path.as_os_str().as_bytes())
+}
+
+/// Convert `OpenOptions` to `dlopen()` mode flags.
+fn flags(options: &OpenOptions) -> c_int {
+    let mut flags = if options.lazy { RTLD_LAZY } else { RTLD_NOW };
+    flags |= if options.global { RTLD_GLOBAL } else { RTLD_LOCAL };
+    if options.nodelete {
+        flags |= RTLD_NODELETE;
+    }
+    if options.noload {
+        flags |= RTLD_NOLOAD;
+    }
+    if options.deepbind {
+        flags |= RTLD_DEEPBIND;
     }
+    flags
 }
@@ -7,24 +7,110 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString, NulError};
 use std::os::raw::c_void;
+use std::path::Path;
 use std::ptr::NonNull;
 
+use crate::manual::{OpenOptions, SymbolInfo};
+
 /// Dynamically loaded library API.
 #[derive(Debug)]
 pub(super) struct DlApi;
 
 impl DlApi {
     /// Load a Dynamic Library API
-    pub(super) fn new(filename: &CStr) -> Option<Self> {
+    pub(super) fn new(
+        filename: &CStr,
+        options: &OpenOptions,
+    ) -> Result<Self, String> {
+        let _ = (filename, options);
+        Ok(DlApi)
+    }
+
+    /// Get a handle to a library only if it's already loaded.
+    pub(super) fn get_if_loaded(filename: &CStr) -> Option<Self> {
         let _ = filename;
-        Some(DlApi)
+        None
+    }
+
+    /// Open the main program.
+    pub(super) fn process() -> Result<Self, String> {
+        Ok(DlApi)
+    }
+
+    /// Pseudo-handle that searches the global scope.
+    pub(super) fn default_scope() -> Self {
+        DlApi
+    }
+
+    /// Pseudo-handle that searches the objects loaded after this one.
+    pub(super) fn next_scope() -> Self {
+        DlApi
     }
 
     /// Get a function or global from the library.
-    pub(super) fn get(&self, symbol: &CStr) -> Option<NonNull<c_void>> {
+    pub(super) fn get(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<NonNull<c_void>>, String> {
         let _ = symbol;
-        None
+        Err("Not supported on this platform".to_string())
+    }
+
+    /// Get the address of a thread-local variable for the calling thread.
+    pub(super) fn get_tls(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<NonNull<c_void>>, String> {
+        let _ = symbol;
+        Err("Not supported on this platform".to_string())
+    }
+
+    /// Check whether two handles are for the same library.
+    pub(super) fn is_same(&self, other: &Self) -> bool {
+        let _ = other;
+        false
     }
 }
+
+/// A copy of a library's bytes that can be loaded.
+#[derive(Debug)]
+pub(super) struct LibraryFile;
+
+impl LibraryFile {
+    /// Write the bytes somewhere they can be loaded from.
+    pub(super) fn new(bytes: &[u8]) -> std::io::Result<Self> {
+        let _ = bytes;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "Not supported on this platform",
+        ))
+    }
+
+    /// Get the path to load the library from.
+    pub(super) fn path(&self) -> &CStr {
+        unreachable!()
+    }
+
+    /// Check whether this is a memory file, rather than a temporary file.
+    pub(super) fn is_memory(&self) -> bool {
+        false
+    }
+
+    /// Move a memory file to a new path.
+    pub(super) fn renumber(&mut self) -> std::io::Result<()> {
+        unreachable!()
+    }
+}
+
+/// Convert a path to a NUL-terminated string.
+pub(super) fn path_to_cstring(path: &Path) -> Result<CString, NulError> {
+    CString::new(path.to_string_lossy().into_owned())
+}
+
+/// Find the library and symbol containing an address.
+pub(super) fn symbol_info(address: *const c_void) -> Option<SymbolInfo> {
+    let _ = address;
+    None
+}
@@ -9,7 +9,10 @@
 
 #![cfg_attr(feature = "docs-rs", feature(external_doc))]
 #![cfg_attr(feature = "docs-rs", doc(include = "../README.md"))]
-#![doc = ""]
+#![cfg_attr(
+    not(feature = "docs-rs"),
+    doc = "Load dynamic (shared object) libraries at runtime."
+)]
 #![doc(
     html_logo_url = "https://libcala.github.io/logo.svg",
     html_favicon_url = "https://libcala.github.io/icon.svg",
@@ -64,4 +67,4 @@ mod linker;
 /// instead.
 pub mod manual;
 
-pub use error::Error;
+pub use error::{Error, LoaderError};
@@ -9,6 +9,10 @@
 
 /// Macro to define the dynamic library API runtime linker struct.
 ///
+/// The library stays loaded for as long as the struct exists, and is unloaded
+/// when it's dropped.  Don't keep copies of the function pointers around after
+/// that.
+///
 /// ```no_run
 /// // Shared object: either "libmylibrary.so.1", "mylibrary-1.dll"
 /// // or "libMyLibrary.dylib"
@@ -24,63 +28,1125 @@
 ///     };
 /// }
 /// ```
+///
+/// If the library might be installed under one of several names, list them in
+/// order of preference, or list the acceptable major versions after the base
+/// name.  `dl_api()` gets the [`DlApi`](crate::manual::DlApi), and its
+/// [`name()`](crate::manual::DlApi::name) tells which one was loaded.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" MyApi ["libmylibrary.so.1", "libmylibrary.so"] {
+///     fn cFunction(param_name: *mut u32) -> u32;
+/// });
+///
+/// // Tries "libother.so.3", then "libother.so.2", then "libother.so".
+/// dl_api::linker!(extern "C" OtherApi "libother.so" versions [3, 2] {
+///     fn otherFunction() -> u32;
+/// });
+///
+/// let api = MyApi::new().unwrap();
+/// println!("Loaded {}", api.dl_api().name());
+/// ```
+///
+/// To use the usual file names for the platform, give the library name and
+/// major version, and optionally file names to use instead on some operating
+/// systems (see [`LibraryName`](crate::manual::LibraryName)).
+///
+/// ```no_run
+/// // Loads "libmylibrary.so.1" on Linux, or "mylibrary-1.dll" on Windows.
+/// dl_api::linker!(extern "C" MyApi
+///     library("mylibrary", 1; macos = "libMyLibrary.dylib")
+/// {
+///     fn cFunction(param_name: *mut u32) -> u32;
+/// });
+/// ```
+///
+/// The struct's `Debug` output shows the address of each symbol, and the
+/// library and symbol it's in (see [`SymbolInfo`](crate::manual::SymbolInfo)).
+///
+/// To load a library that's embedded in the program, pass its bytes instead of
+/// the file name (see
+/// [`OpenOptions::open_bytes()`](crate::manual::OpenOptions::open_bytes)).
+///
+/// ```no_run
+/// # static PLUGIN: &[u8] = &[];
+/// // static PLUGIN: &[u8] = include_bytes!("libplugin.so");
+/// dl_api::linker!(extern "C" Plugin bytes(PLUGIN) {
+///     fn plugin_init() -> u32;
+/// });
+///
+/// let plugin = Plugin::new().unwrap();
+/// ```
+///
+/// To get symbols from the executable itself, use `process` instead of the
+/// library; `default_scope` and `next_scope` search the global scope
+/// (`RTLD_DEFAULT`) and the libraries after this one (`RTLD_NEXT`).  The
+/// `dlopen()` flags from `with_options()` are ignored.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" Host process {
+///     fn optional_feature() -> u32;
+/// });
+///
+/// let statically_linked = Host::new().is_ok();
+/// ```
+///
+/// The struct is `Send` and `Sync` only if all of the item types are.  To
+/// declare what the library actually supports instead, add a
+/// `#![thread_safety]` attribute at the start of the block: `sync` if it can be
+/// used from any thread at the same time, `send` if it can be moved to another
+/// thread but not shared, or `none` if it must stay on the thread that loaded
+/// it.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+///     #![thread_safety(sync)]
+///
+///     static global_state: *mut u32;
+/// });
+///
+/// let api = std::sync::Arc::new(MyApi::new().unwrap());
+/// std::thread::spawn(move || println!("{:?}", api));
+/// ```
+///
+/// Thread-local variables have a different address on each thread, so
+/// `thread_local static` items are declared with the type of the variable, and
+/// become a [`ThreadLocal`](crate::manual::ThreadLocal) that gets the address
+/// for the calling thread.  They can't have a `#[version]`.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+///     thread_local static mylibrary_errno: i32;
+/// });
+///
+/// let api = MyApi::new().unwrap();
+/// let errno = unsafe { *api.mylibrary_errno.get() };
+/// ```
+///
+/// Items that might legitimately be NULL (like undefined weak symbols) can be
+/// marked `#[nullable]`, which makes the field an `Option`.  Otherwise, a NULL
+/// item is an [`Error::NullSymbol`](crate::Error::NullSymbol).
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+///     #[nullable]
+///     fn optionalHook() -> u32;
+/// });
+///
+/// let api = MyApi::new().unwrap();
+/// if let Some(hook) = api.optionalHook {
+///     unsafe { hook() };
+/// }
+/// ```
+///
+/// Items that only some versions of the library have can be marked
+/// `#[optional]`, which also makes the field an `Option`.  It's `None` if the
+/// library doesn't have the item (or it's NULL), instead of failing to load.
+/// Neither attribute can be used with `thread_local static` items.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+///     fn cFunction(param_name: *mut u32) -> u32;
+///     #[optional]
+///     fn cFunctionAddedIn1_2(param_name: *mut u32) -> u32;
+/// });
+///
+/// let api = MyApi::new().unwrap();
+/// if api.cFunctionAddedIn1_2.is_some() {
+///     println!("Have version 1.2");
+/// }
+/// ```
+///
+/// The symbol is the field name, unless the item has a `#[link_name]`
+/// attribute.  To add a prefix or suffix to the names, add a `#![link_prefix]`
+/// or `#![link_suffix]` attribute at the start of the block (items with
+/// `#[link_name]` don't get them).
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" Sdl "libSDL2-2.0.so.0" {
+///     #![link_prefix = "SDL_"]
+///
+///     fn Init(flags: u32) -> i32; // SDL_Init
+///     #[link_name = "SDL_GetError"]
+///     fn error() -> *const std::os::raw::c_char;
+/// });
+/// ```
+///
+/// If a symbol is named differently in some versions or builds of the library,
+/// add an `#[aliases]` attribute with the other names to try, in order, if the
+/// first one doesn't exist (not for thread-local statics, or with
+/// `#[version]`).  `symbol_name()` returns the name that a field was found by.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" Foo "libfoo.so" {
+///     #[aliases("foo_v2", "_foo")]
+///     fn foo(param_name: *mut u32) -> u32;
+/// });
+///
+/// let api = Foo::new().unwrap();
+/// println!("Using {}", api.symbol_name("foo").unwrap());
+/// ```
+///
+/// To call a function without `unsafe`, mark it `#[safe]`, which generates a
+/// method with the same name as the field (or `#[safe(method_name)]` for a
+/// different name).  This promises that no arguments can make the function
+/// cause undefined behavior, so pointer parameters usually need a conversion:
+/// `#[cstr]` makes the method take a `&CStr`, and `#[slice]` makes it take a
+/// slice, passed as the pointer and the parameter after it as the length.  The
+/// method panics if the slice is longer than the length's type can hold.
+///
+/// ```no_run
+/// use std::ffi::CStr;
+/// use std::os::raw::c_char;
+///
+/// dl_api::linker!(extern "C" Zlib "libz.so.1" {
+///     #[safe]
+///     fn crc32(crc: u64, #[slice] buf: *const u8, len: u32) -> u64;
+///     #[safe(version)]
+///     fn zlibVersion() -> *const c_char;
+/// });
+/// dl_api::linker!(extern "C" LibC "libc.so.6" {
+///     #[safe]
+///     fn strlen(#[cstr] s: *const c_char) -> usize;
+/// });
+///
+/// let zlib = Zlib::new().unwrap();
+/// println!("CRC-32: {:x}", zlib.crc32(0, b"Hello, world!"));
+/// let libc = LibC::new().unwrap();
+/// let hi = CStr::from_bytes_with_nul(b"Hi\0").unwrap();
+/// println!("Length: {}", libc.strlen(hi));
+/// ```
+///
+/// To look up a function the first time it's used, instead of when the library
+/// is loaded, mark it `#[lazy]`, or add `#![lazy]` at the start of the block
+/// for all of the functions.  The field becomes a
+/// [`Lazy`](crate::manual::Lazy): dereferencing it panics if the function
+/// can't be found, and `get()` returns an error instead (so lazy functions
+/// can't also be `#[optional]` or `#[nullable]`, and can't have a
+/// `#[version]`).  Their symbol names still come from `#[link_name]`,
+/// `#[aliases]` and the block's prefix and suffix, and they can be `#[safe]`.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" Gl "libGL.so.1" {
+///     #![lazy]
+///
+///     fn glClear(mask: u32) -> ();
+///     fn glFlush() -> ();
+/// });
+///
+/// let gl = Gl::new().unwrap();
+/// unsafe { (*gl.glClear)(0x4000) }; // Panics if `glClear` doesn't exist
+/// match gl.glFlush.get() {
+///     Ok(gl_flush) => unsafe { gl_flush() },
+///     Err(error) => eprintln!("{}", error),
+/// }
+/// ```
+///
+/// To get a specific version of a symbol (Linux with glibc only), add a
+/// `#[version]` attribute.
+///
+/// ```no_run
+/// # #[cfg(all(target_os = "linux", target_env = "gnu"))]
+/// dl_api::linker!(extern "C" LibC "libc.so.6" {
+///     #[version = "GLIBC_2.2.5"]
+///     fn memcpy(dest: *mut u8, src: *const u8, n: usize) -> *mut u8;
+/// });
+/// ```
+///
+/// Items can also have doc comments.  Any other attribute is an error.
+///
+/// ```no_run
+/// dl_api::linker!(extern "C" Vulkan "libvulkan.so.1" {
+///     /// Creates a surface for an X11 window.
+///     fn vkCreateXlibSurfaceKHR(
+///         instance: *mut u8,
+///         info: *const u8,
+///         allocator: *const u8,
+///         surface: *mut u64,
+///     ) -> i32;
+/// });
+/// ```
+///
+/// To avoid loading the library and looking up the items again each time, call
+/// `shared()` instead of `new()`.  It returns the same `Arc` until all of its
+/// clones are dropped (only if the struct is `Send` and `Sync`).
+///
+/// ```no_run
+/// # dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+/// #     fn cFunction(param_name: *mut u32) -> u32;
+/// # });
+/// let api = MyApi::shared().unwrap();
+/// let same_api = MyApi::shared().unwrap();
+/// ```
+///
+/// Copies of the items mustn't be used after the struct is dropped: only the
+/// struct itself and items got with `leased()` (see
+/// [`Leased`](crate::manual::Leased)) keep the library loaded.  Calling
+/// `unload()` waits for the leases to be dropped before dropping the struct,
+/// and gives the struct back if they aren't dropped before the timeout.
+///
+/// ```no_run
+/// # dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+/// #     fn cFunction(param_name: *mut u32) -> u32;
+/// # });
+/// let api = MyApi::new().unwrap();
+/// let c_function = api.leased(|api| api.cFunction);
+/// drop(api);
+/// let rtn = unsafe { (*c_function)(std::ptr::null_mut()) };
+///
+/// let api = MyApi::new().unwrap();
+/// let c_function = api.leased(|api| api.cFunction);
+/// let timeout = std::time::Duration::from_millis(100);
+/// let api = api.unload(Some(timeout)).unwrap_err(); // Still leased
+/// drop(c_function);
+/// api.unload(None).unwrap();
+/// ```
+///
+/// To reload the library when it's rebuilt, wrap the struct in a
+/// [`Reloadable`](crate::manual::Reloadable).
+///
+/// To load the library with different `dlopen()` flags, pass
+/// [`OpenOptions`](crate::manual::OpenOptions) to `with_options()` instead of
+/// calling `new()`.
+///
+/// ```no_run
+/// # dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+/// #     fn cFunction(param_name: *mut u32) -> u32;
+/// # });
+/// use dl_api::manual::OpenOptions;
+///
+/// let api = MyApi::with_options(OpenOptions::new().global(true)).unwrap();
+/// ```
+///
+/// To load the library from a path that's only known at runtime, call `open()`
+/// instead, or `from_handle()` with an already loaded
+/// [`DlApi`](crate::manual::DlApi).
+///
+/// ```no_run
+/// # dl_api::linker!(extern "C" MyApi "libmylibrary.so.1" {
+/// #     fn cFunction(param_name: *mut u32) -> u32;
+/// # });
+/// let exe = std::env::current_exe().unwrap();
+/// let api = MyApi::open(exe.with_file_name("libmylibrary.so.1")).unwrap();
+/// ```
 #[macro_export]
 macro_rules! linker(
-	(extern $abi: literal/*item*/ $sname: ident $filename: literal {
-      $(static $data:ident : $darg:ty;)*
-      $(valist fn $vafn:ident ($($varg:ident : $fvrg:ty),* , ...) -> $frvt:ty;)*
-      $(fn $name:ident ($($sarg:ident : $farg:ty),* $(,)?) -> $fret:ty;)*
+    (extern $abi:literal $sname:ident bytes($bytes:expr) {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname {bytes $bytes} {
+            $($body)*
+        });
+    };
+    (extern $abi:literal $sname:ident process {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname {process} { $($body)* });
+    };
+    (extern $abi:literal $sname:ident default_scope {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname {default_scope} {
+            $($body)*
+        });
+    };
+    (extern $abi:literal $sname:ident next_scope {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname {next_scope} {
+            $($body)*
+        });
+    };
+    (extern $abi:literal $sname:ident $filename:literal {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname [$filename] { $($body)* });
+    };
+    (extern $abi:literal $sname:ident [$($filename:literal),+ $(,)?] {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname [$($filename),+] {
+            $($body)*
+        });
+    };
+    (extern $abi:literal $sname:ident
+        $base:literal versions [$($version:literal),+ $(,)?]
+    {
+        $($body:tt)*
+    }) => {
+        $crate::linker!(@impl extern $abi $sname [
+            $(concat!($base, ".", $version),)+ $base
+        ] {
+            $($body)*
+        });
+    };
+    (extern $abi:literal $sname:ident
+        library($name:literal $(, $version:literal)?
+            $(; $($os:ident = $target:literal),+ $(,)?)?)
+    {
+        $($body:tt)*
     }) => {
-		#[allow(non_snake_case)]
-		struct $sname {
-		    $( $data: $darg, )*
-			$( $vafn: unsafe extern $abi fn($($fvrg),*, ...) -> $frvt, )*
-			$( $name: unsafe extern $abi fn($($farg),*) -> $fret, )*
-		}
+        $crate::linker!(@impl extern $abi $sname (
+            $crate::manual::LibraryName::new($name)
+                $(.version($version))?
+                $($(.target(stringify!($os), $target))+)?
+                .candidates()
+        ) {
+            $($body)*
+        });
+    };
+    (@open $options:ident {bytes $bytes:expr}) => {
+        $options.open_bytes($bytes)
+    };
+    (@open $options:ident {process}) => {
+        $crate::manual::DlApi::process()
+    };
+    (@open $options:ident {$scope:ident}) => {
+        ::std::result::Result::<_, $crate::Error>::Ok(
+            $crate::manual::DlApi::$scope()
+        )
+    };
+    (@open $options:ident ($candidates:expr)) => {
+        $options.open_first($candidates).map(|loaded| loaded.dl_api)
+    };
+    (@open $options:ident [$filename:expr]) => {
+        $options.open($filename)
+    };
+    (@open $options:ident [$($filename:expr),+]) => {
+        $options
+            .open_first(&[$($filename),+])
+            .map(|loaded| loaded.dl_api)
+    };
+    // Parse each item into the same shape, so that they can be matched by a
+    // single repetition (attributes on more than one repetition would be
+    // ambiguous).  Keywords before the name are matched as identifiers, and
+    // the field name is moved to the front.
+    (@impl extern $abi:literal $sname:ident $source:tt {
+        $(#![$($battr:tt)*])*
+        $(
+            $(#[$($attr:tt)*])*
+            $kind:ident $name:ident $($kname:ident)?
+            $(($($arg:tt)*) -> $ret:ty)?
+            $(: $ty:ty)?;
+        )*
+    }) => {
+        $crate::linker!(@items extern $abi $sname $source
+            [$(#![$($battr)*])*]
+        {
+            $(
+                {$($kname)? $name}
+                [$(#[$($attr)*])*]
+                ($kind $name $($kname)?)
+                ($(($($arg)*) -> $ret)? $(: $ty)?);
+            )*
+        });
+    };
+    (@items extern $abi:literal $sname:ident $source:tt $battrs:tt {
+        $({$field:ident $($keyword:ident)?} $attrs:tt $kind:tt $sig:tt;)*
+    }) => {
+        #[allow(non_snake_case)]
+        struct $sname {
+            $(
+                $field: $crate::linker!(@if_lazy $battrs $attrs $kind {
+                    $crate::manual::Lazy<$crate::linker!(@type $abi $kind $sig)>
+                } {
+                    $crate::linker!(@field $attrs $abi $kind $sig)
+                }),
+            )*
+            // Keeps the library loaded for as long as the pointers are used.
+            __dl_api: $crate::manual::DlApi,
+            // Symbol that each field was found by (except lazy ones).
+            __symbol_names: ::std::vec::Vec<(&'static str, &'static str)>,
+            // Opts out of auto traits that the library doesn't support.
+            __thread_safety: $crate::linker!(@marker $battrs),
+        }
+
+        $crate::linker!(@check_block $battrs);
+        $($crate::linker!(@check $battrs $attrs $kind);)*
+        $crate::linker!(@thread_safety $sname $battrs);
+
+        impl $sname {
+            fn new() -> ::std::result::Result<Self, $crate::Error> {
+                Self::with_options(&$crate::manual::OpenOptions::new())
+            }
+
+            #[allow(dead_code)]
+            fn with_options(options: &$crate::manual::OpenOptions)
+                -> ::std::result::Result<Self, $crate::Error>
+            {
+                Self::from_handle(
+                    $crate::linker!(@open options $source)?
+                )
+            }
+
+            #[allow(dead_code)]
+            fn open<P: ::std::convert::AsRef<::std::path::Path>>(path: P)
+                -> ::std::result::Result<Self, $crate::Error>
+            {
+                Self::from_handle($crate::manual::DlApi::open(path)?)
+            }
+
+            #[allow(dead_code)]
+            fn shared() -> ::std::result::Result<
+                ::std::sync::Arc<Self>,
+                $crate::Error,
+            >
+            where
+                // Only callable if the struct can be shared between threads.
+                for<'a> Self: ::std::marker::Send + ::std::marker::Sync,
+            {
+                $crate::manual::shared_instance(Self::new)
+            }
+
+            #[allow(dead_code)]
+            fn leased<F, T>(&self, item: F) -> $crate::manual::Leased<T>
+            where
+                F: ::std::ops::FnOnce(&Self) -> T,
+            {
+                self.__dl_api.leased(item(self))
+            }
+
+            #[allow(dead_code)]
+            fn unload(
+                self,
+                timeout: ::std::option::Option<::std::time::Duration>,
+            ) -> ::std::result::Result<(), Self>
+            {
+                if self.__dl_api.wait_for_leases(timeout) {
+                    ::std::result::Result::Ok(())
+                } else {
+                    ::std::result::Result::Err(self)
+                }
+            }
 
-		impl $sname {
-			fn new() -> ::std::result::Result<Self, $crate::Error> {
-				unsafe {
-    				const FILENAME: &str = concat!($filename, "\0");
-	    		    let dl_api = $crate::manual::DlApi::new(
-    	    		    ::std::ffi::CStr::from_bytes_with_nul_unchecked(
-    	    		        FILENAME.as_bytes()
-	    		        )
-	    		    ).ok_or($crate::Error::NotInstalled)?;
+            #[allow(dead_code)]
+            fn dl_api(&self) -> &$crate::manual::DlApi {
+                &self.__dl_api
+            }
+
+            #[allow(dead_code)]
+            fn symbol_name(&self, field: &str)
+                -> ::std::option::Option<&str>
+            {
+                $(
+                    $crate::linker!(@if_lazy $battrs $attrs $kind {
+                        if field == stringify!($field) {
+                            return self.$field
+                                .symbol_name()
+                                .and_then(|name| name.to_str().ok());
+                        }
+                    } {});
+                )*
+                self.__symbol_names
+                    .iter()
+                    .find(|(name, _)| *name == field)
+                    .map(|(_, symbol)| *symbol)
+            }
+
+            #[allow(dead_code)]
+            fn from_handle(dl_api: $crate::manual::DlApi)
+                -> ::std::result::Result<Self, $crate::Error>
+            {
+                #[allow(unused_mut)]
+                let mut symbol_names = ::std::vec::Vec::new();
+                unsafe {
                     ::std::result::Result::<Self, $crate::Error>::Ok(Self {
                         $(
-                            $data: {
-                                const NAME: &str = concat!(stringify!($data), "\0");
-                				::std::mem::transmute(dl_api.get(
-                    				::std::ffi::CStr::from_bytes_with_nul_unchecked(
-                    				    NAME.as_bytes()
-                    				)
-                				).ok_or($crate::Error::DoesntExist(stringify!($data)))?)
-                            },
+                            $field: $crate::linker!(
+                                @if_lazy $battrs $attrs $kind {
+                                    $crate::linker!(@lazy dl_api
+                                        ($crate::linker!(
+                                            @name $battrs $attrs $field
+                                        ))
+                                        $attrs
+                                    )
+                                } {
+                                    $crate::linker!(
+                                        @init (dl_api symbol_names $field)
+                                            $battrs $kind $attrs
+                                    )
+                                }
+                            ),
                         )*
-                        $(
-                            $vafn: {
-                				const NAME: &str = concat!(stringify!($vafn), "\0");
-                				::std::mem::transmute(dl_api.get(
-                    				::std::ffi::CStr::from_bytes_with_nul_unchecked(
-                    				    NAME.as_bytes()
-                    				)
-                				).ok_or($crate::Error::DoesntExist(stringify!($vafn)))?)
-            				},
-        				)*
-                        $(
-                            $name: {
-                				const NAME: &str = concat!(stringify!($name), "\0");
-                				::std::mem::transmute(dl_api.get(
-                    				::std::ffi::CStr::from_bytes_with_nul_unchecked(
-                    				    NAME.as_bytes()
-                    				)
-                				).ok_or($crate::Error::DoesntExist(stringify!($name)))?)
-            				},
-        				)*
-    				})
-				}
-			}
-		}
-	};
+                        __dl_api: dl_api,
+                        __symbol_names: symbol_names,
+                        __thread_safety: ::std::default::Default::default(),
+                    })
+                }
+            }
+
+            $(
+                $crate::linker!(
+                    @method $battrs $attrs $attrs $kind $field $sig
+                );
+            )*
+        }
+
+        impl $crate::manual::Linker for $sname {
+            fn from_handle(dl_api: $crate::manual::DlApi)
+                -> ::std::result::Result<Self, $crate::Error>
+            {
+                $sname::from_handle(dl_api)
+            }
+
+            fn dl_api(&self) -> &$crate::manual::DlApi {
+                &self.__dl_api
+            }
+        }
+
+        impl ::std::fmt::Debug for $sname {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>)
+                -> ::std::fmt::Result
+            {
+                let mut debug = f.debug_struct(stringify!($sname));
+                $(
+                    let address: *const ::std::os::raw::c_void = unsafe {
+                        $crate::linker!(@if_lazy $battrs $attrs $kind {
+                            match self.$field.resolved() {
+                                ::std::option::Option::Some(item) => {
+                                    ::std::mem::transmute_copy(&item)
+                                }
+                                ::std::option::Option::None => {
+                                    ::std::ptr::null()
+                                }
+                            }
+                        } {
+                            $crate::linker!(
+                                @address $kind $attrs (self.$field)
+                            )
+                        })
+                    };
+                    match $crate::manual::SymbolInfo::new(address) {
+                        Some(info) => debug.field(
+                            stringify!($field),
+                            &format_args!("{:p} ({})", address, info),
+                        ),
+                        None => debug.field(stringify!($field), &address),
+                    };
+                )*
+                debug.finish()
+            }
+        }
+    };
+    // Attributes that are accepted on the block and on each item
+    (@check_block [#![thread_safety $($args:tt)*] $($battr:tt)*]) => {
+        $crate::linker!(@check_block [$($battr)*]);
+    };
+    (@check_block [#![lazy] $($battr:tt)*]) => {
+        $crate::linker!(@check_block [$($battr)*]);
+    };
+    (@check_block [#![link_prefix = $prefix:literal] $($battr:tt)*]) => {
+        $crate::linker!(@check_block [$($battr)*]);
+    };
+    (@check_block [#![link_suffix = $suffix:literal] $($battr:tt)*]) => {
+        $crate::linker!(@check_block [$($battr)*]);
+    };
+    (@check_block [#![doc $($args:tt)*] $($battr:tt)*]) => {
+        $crate::linker!(@check_block [$($battr)*]);
+    };
+    (@check_block [#![$($other:tt)*] $($battr:tt)*]) => {
+        compile_error!(concat!(
+            "Unknown attribute `#![", stringify!($($other)*), "]`"
+        ));
+    };
+    (@check_block []) => {};
+    (@check $battrs:tt $attrs:tt $kind:tt) => {
+        $crate::linker! {@if_lazy $battrs $attrs $kind {
+            $crate::linker!(@check_item (lazy) $kind $attrs);
+        } {
+            $crate::linker!(@check_item () $kind $attrs);
+        }}
+    };
+    (@check_item () $kind:tt [#[lazy] $($attr:tt)*]) => {
+        compile_error!("`#[lazy]` is only for functions");
+    };
+    (@check_item (lazy) $kind:tt [#[version = $v:literal] $($attr:tt)*]) => {
+        compile_error!("`#[version]` can't be used with `lazy`");
+    };
+    (@check_item (lazy) $kind:tt [#[nullable] $($attr:tt)*]) => {
+        compile_error!("`#[nullable]` can't be used with `lazy`");
+    };
+    (@check_item (lazy) $kind:tt [#[optional] $($attr:tt)*]) => {
+        compile_error!("`#[optional]` can't be used with `lazy`");
+    };
+    (@check_item () (thread_local static $name:ident)
+        [#[nullable] $($attr:tt)*]
+    ) => {
+        compile_error!("`#[nullable]` can't be used with `thread_local`");
+    };
+    (@check_item () (thread_local static $name:ident)
+        [#[optional] $($attr:tt)*]
+    ) => {
+        compile_error!("`#[optional]` can't be used with `thread_local`");
+    };
+    (@check_item () (thread_local static $name:ident)
+        [#[version $($args:tt)*] $($attr:tt)*]
+    ) => {
+        compile_error!("`#[version]` can't be used with `thread_local`");
+    };
+    (@check_item () (thread_local static $name:ident)
+        [#[aliases $($args:tt)*] $($attr:tt)*]
+    ) => {
+        compile_error!("`#[aliases]` can't be used with `thread_local`");
+    };
+    (@check_item $lazy:tt $kind:tt [#[doc $($args:tt)*] $($attr:tt)*]) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt
+        [#[link_name = $name:literal] $($attr:tt)*]
+    ) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt
+        [#[aliases($($alias:literal),+ $(,)?)] $($attr:tt)*]
+    ) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt [#[version = $v:literal] $($attr:tt)*]) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt [#[safe $(($method:ident))?] $($attr:tt)*]
+    ) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt [#[lazy] $($attr:tt)*]) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt [#[nullable] $($attr:tt)*]) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt [#[optional] $($attr:tt)*]) => {
+        $crate::linker!(@check_item $lazy $kind [$($attr)*]);
+    };
+    (@check_item $lazy:tt $kind:tt [#[$($other:tt)*] $($attr:tt)*]) => {
+        compile_error!(concat!(
+            "Unknown attribute `#[", stringify!($($other)*), "]`"
+        ));
+    };
+    (@check_item $lazy:tt $kind:tt []) => {};
+    // Auto traits, from the `thread_safety` block attribute
+    (@thread_safety $sname:ident
+        [#![thread_safety(sync)] $($battr:tt)*]
+    ) => {
+        unsafe impl ::std::marker::Send for $sname {}
+        unsafe impl ::std::marker::Sync for $sname {}
+    };
+    (@thread_safety $sname:ident
+        [#![thread_safety(send)] $($battr:tt)*]
+    ) => {
+        unsafe impl ::std::marker::Send for $sname {}
+    };
+    (@thread_safety $sname:ident
+        [#![thread_safety(none)] $($battr:tt)*]
+    ) => {};
+    (@thread_safety $sname:ident
+        [#![thread_safety $($other:tt)*] $($battr:tt)*]
+    ) => {
+        compile_error!("Expected `thread_safety(sync)`, `(send)` or `(none)`");
+    };
+    (@thread_safety $sname:ident [#![$($other:tt)*] $($battr:tt)*]) => {
+        $crate::linker!(@thread_safety $sname [$($battr)*]);
+    };
+    (@thread_safety $sname:ident []) => {};
+    (@marker [#![thread_safety(send)] $($battr:tt)*]) => {
+        ::std::marker::PhantomData<::std::cell::Cell<()>>
+    };
+    (@marker [#![thread_safety(none)] $($battr:tt)*]) => {
+        ::std::marker::PhantomData<*const ()>
+    };
+    (@marker [#![thread_safety $($other:tt)*] $($battr:tt)*]) => { () };
+    (@marker [#![$($other:tt)*] $($battr:tt)*]) => {
+        $crate::linker!(@marker [$($battr)*])
+    };
+    (@marker []) => { () };
+    // Choose tokens depending on whether a function is resolved lazily, by a
+    // `lazy` attribute on the block or on the item
+    (@if_lazy [#![lazy] $($battr:tt)*] $attrs:tt ($(valist)? fn $name:ident)
+        {$($then:tt)*} $else:tt
+    ) => {
+        $($then)*
+    };
+    (@if_lazy [#![$($other:tt)*] $($battr:tt)*] $attrs:tt $kind:tt
+        $then:tt $else:tt
+    ) => {
+        $crate::linker! {@if_lazy [$($battr)*] $attrs $kind $then $else}
+    };
+    (@if_lazy [] [#[lazy] $($attr:tt)*] ($(valist)? fn $name:ident)
+        {$($then:tt)*} $else:tt
+    ) => {
+        $($then)*
+    };
+    (@if_lazy [] [#[$($other:tt)*] $($attr:tt)*] $kind:tt
+        $then:tt $else:tt
+    ) => {
+        $crate::linker! {@if_lazy [] [$($attr)*] $kind $then $else}
+    };
+    (@if_lazy [] [] $kind:tt $then:tt {$($else:tt)*}) => {
+        $($else)*
+    };
+    // Field types, wrapped in `Option` if the item's attributes allow NULL
+    // (thread-local statics can't be, which `@check` reports)
+    (@field $attrs:tt $abi:literal (thread_local static $name:ident)
+        $sig:tt
+    ) => {
+        $crate::linker!(@type $abi (thread_local static $name) $sig)
+    };
+    (@field [#[nullable] $($attr:tt)*] $abi:literal $kind:tt $sig:tt) => {
+        ::std::option::Option<$crate::linker!(@type $abi $kind $sig)>
+    };
+    (@field [#[optional] $($attr:tt)*] $abi:literal $kind:tt $sig:tt) => {
+        ::std::option::Option<$crate::linker!(@type $abi $kind $sig)>
+    };
+    (@field [#[$($other:tt)*] $($attr:tt)*] $abi:literal $kind:tt $sig:tt) => {
+        $crate::linker!(@field [$($attr)*] $abi $kind $sig)
+    };
+    (@field [] $abi:literal $kind:tt $sig:tt) => {
+        $crate::linker!(@type $abi $kind $sig)
+    };
+    // Field addresses, for `Debug`
+    (@address (thread_local static $name:ident) $attrs:tt ($field:expr)) => {
+        $field.get().cast()
+    };
+    (@address $kind:tt [#[nullable] $($attr:tt)*] ($field:expr)) => {
+        $crate::linker!(@address $kind [#[optional]] ($field))
+    };
+    (@address $kind:tt [#[optional] $($attr:tt)*] ($field:expr)) => {
+        match &$field {
+            ::std::option::Option::Some(item) => {
+                ::std::mem::transmute_copy(item)
+            }
+            ::std::option::Option::None => ::std::ptr::null(),
+        }
+    };
+    (@address $kind:tt [#[$($other:tt)*] $($attr:tt)*] ($field:expr)) => {
+        $crate::linker!(@address $kind [$($attr)*] ($field))
+    };
+    (@address $kind:tt [] ($field:expr)) => {
+        ::std::mem::transmute_copy(&$field)
+    };
+    // Item types
+    (@type $abi:literal (static $name:ident) (: $ty:ty)) => { $ty };
+    (@type $abi:literal (thread_local static $name:ident) (: $ty:ty)) => {
+        $crate::manual::ThreadLocal<$ty>
+    };
+    (@type $abi:literal ($(valist)? fn $name:ident) ($args:tt -> $ret:ty)) => {
+        $crate::linker!(@fn $abi $args -> $ret)
+    };
+    (@fn $abi:literal
+        ($($(#[$($pattr:tt)*])* $arg:ident : $ty:ty),* $(,)?) -> $ret:ty
+    ) => {
+        unsafe extern $abi fn($($ty),*) -> $ret
+    };
+    (@fn $abi:literal
+        ($($(#[$($pattr:tt)*])* $arg:ident : $ty:ty),+ , ...) -> $ret:ty
+    ) => {
+        unsafe extern $abi fn($($ty),+, ...) -> $ret
+    };
+    // Safe methods, from the `safe` attribute
+    (@method $battrs:tt $attrs:tt [#[safe] $($attr:tt)*] $kind:tt
+        $field:ident $sig:tt
+    ) => {
+        $crate::linker!(@safe $battrs $attrs $kind $field $field $sig);
+    };
+    (@method $battrs:tt $attrs:tt [#[safe($method:ident)] $($attr:tt)*]
+        $kind:tt $field:ident $sig:tt
+    ) => {
+        $crate::linker!(@safe $battrs $attrs $kind $field $method $sig);
+    };
+    (@method $battrs:tt $attrs:tt [#[$($other:tt)*] $($attr:tt)*] $kind:tt
+        $field:ident $sig:tt
+    ) => {
+        $crate::linker!(@method $battrs $attrs [$($attr)*] $kind $field $sig);
+    };
+    (@method $battrs:tt $attrs:tt [] $kind:tt $field:ident $sig:tt) => {};
+    (@safe $battrs:tt $attrs:tt (fn $name:ident) $field:ident
+        $method:ident $sig:tt
+    ) => {
+        $crate::linker!(@if_lazy $battrs $attrs (fn $name) {
+            $crate::linker!(@wrapper (*) $field $method $sig);
+        } {
+            $crate::linker!(@eager $attrs $field $method $sig);
+        });
+    };
+    (@safe $battrs:tt $attrs:tt (valist fn $name:ident) $field:ident
+        $method:ident $sig:tt
+    ) => {
+        compile_error!("`#[safe]` isn't supported for variadic functions");
+    };
+    (@safe $battrs:tt $attrs:tt $kind:tt $field:ident $method:ident
+        $sig:tt
+    ) => {
+        compile_error!("`#[safe]` is only for functions");
+    };
+    (@eager [#[nullable] $($attr:tt)*] $field:ident $method:ident $sig:tt) => {
+        compile_error!("`#[safe]` can't be combined with `#[nullable]`");
+    };
+    (@eager [#[optional] $($attr:tt)*] $field:ident $method:ident $sig:tt) => {
+        compile_error!("`#[safe]` can't be combined with `#[optional]`");
+    };
+    (@eager [#[$($other:tt)*] $($attr:tt)*] $field:ident $method:ident
+        $sig:tt
+    ) => {
+        $crate::linker!(@eager [$($attr)*] $field $method $sig);
+    };
+    (@eager [] $field:ident $method:ident $sig:tt) => {
+        $crate::linker!(@wrapper () $field $method $sig);
+    };
+    (@wrapper $deref:tt $field:ident $method:ident
+        (($($params:tt)*) -> $ret:ty)
+    ) => {
+        $crate::linker!(@params $deref $field $method $ret [] []
+            ($($params)*)
+        );
+    };
+    // Method parameters, converted to the function's arguments
+    (@params $deref:tt $field:ident $method:ident $ret:ty [$($param:tt)*]
+        [$($arg:tt)*] (#[cstr] $name:ident : $ty:ty $(, $($rest:tt)*)?)
+    ) => {
+        $crate::linker!(@params $deref $field $method $ret
+            [$($param)* $name: &::std::ffi::CStr,]
+            [$($arg)* $name.as_ptr(),]
+            ($($($rest)*)?)
+        );
+    };
+    (@params $deref:tt $field:ident $method:ident $ret:ty [$($param:tt)*]
+        [$($arg:tt)*] (#[slice] $name:ident : *const $elem:ty,
+            $len:ident : $len_ty:ty $(, $($rest:tt)*)?)
+    ) => {
+        $crate::linker!(@params $deref $field $method $ret
+            [$($param)* $name: &[$elem],]
+            [$($arg)* $name.as_ptr(), $crate::linker!(@len $name $len_ty),]
+            ($($($rest)*)?)
+        );
+    };
+    (@params $deref:tt $field:ident $method:ident $ret:ty [$($param:tt)*]
+        [$($arg:tt)*] (#[slice] $name:ident : *mut $elem:ty,
+            $len:ident : $len_ty:ty $(, $($rest:tt)*)?)
+    ) => {
+        $crate::linker!(@params $deref $field $method $ret
+            [$($param)* $name: &mut [$elem],]
+            [$($arg)* $name.as_mut_ptr(), $crate::linker!(@len $name $len_ty),]
+            ($($($rest)*)?)
+        );
+    };
+    (@params $deref:tt $field:ident $method:ident $ret:ty [$($param:tt)*]
+        [$($arg:tt)*] ($name:ident : $ty:ty $(, $($rest:tt)*)?)
+    ) => {
+        $crate::linker!(@params $deref $field $method $ret
+            [$($param)* $name: $ty,]
+            [$($arg)* $name,]
+            ($($($rest)*)?)
+        );
+    };
+    (@params ($($deref:tt)?) $field:ident $method:ident $ret:ty
+        [$($param:tt)*] [$($arg:tt)*] ()
+    ) => {
+        #[allow(dead_code, non_snake_case)]
+        fn $method(&self, $($param)*) -> $ret {
+            unsafe { ($($deref)? self.$field)($($arg)*) }
+        }
+    };
+    (@len $slice:ident $len_ty:ty) => {
+        <$len_ty as ::std::convert::TryFrom<usize>>::try_from($slice.len())
+            .expect("Slice is too long for the function")
+    };
+    // Field values
+    (@lazy $dl_api:ident ($name:expr)
+        [#[aliases($($alias:literal),+ $(,)?)] $($attr:tt)*]
+    ) => {
+        $crate::manual::Lazy::first(&$dl_api, &[
+            $crate::linker!(@cstr $name),
+            $($crate::linker!(@cstr $alias)),+
+        ])
+    };
+    (@lazy $dl_api:ident ($name:expr) [#[$($other:tt)*] $($attr:tt)*]) => {
+        $crate::linker!(@lazy $dl_api ($name) [$($attr)*])
+    };
+    (@lazy $dl_api:ident ($name:expr) []) => {
+        $crate::manual::Lazy::new(&$dl_api, $crate::linker!(@cstr $name))
+    };
+    (@init ($dl_api:ident $names:ident $field:ident) $battrs:tt
+        (thread_local static $name:ident) $attrs:tt
+    ) => {{
+        let name = $crate::linker!(@name $battrs $attrs $field);
+        let item = $crate::manual::ThreadLocal::new(
+            &$dl_api,
+            $crate::linker!(@cstr $crate::linker!(@name $battrs $attrs $field)),
+        )?;
+        $names.push((stringify!($field), name));
+        item
+    }};
+    (@init ($dl_api:ident $names:ident $field:ident) $battrs:tt $kind:tt
+        $attrs:tt
+    ) => {
+        $crate::linker!(@get ($dl_api $names $field)
+            ($crate::linker!(@name $battrs $attrs $field)) () () () $attrs
+        )
+    };
+    // Symbol names, from the `link_name` attribute, or the field name with the
+    // block's `link_prefix` and `link_suffix`
+    (@name $battrs:tt [#[link_name = $link_name:literal] $($attr:tt)*]
+        $field:ident
+    ) => {
+        $link_name
+    };
+    (@name $battrs:tt [#[$($other:tt)*] $($attr:tt)*] $field:ident) => {
+        $crate::linker!(@name $battrs [$($attr)*] $field)
+    };
+    (@name $battrs:tt [] $field:ident) => {
+        $crate::linker!(@affix $battrs ("") ("") $field)
+    };
+    (@affix [#![link_prefix = $prefix:literal] $($battr:tt)*]
+        $old:tt $suffix:tt $field:ident
+    ) => {
+        $crate::linker!(@affix [$($battr)*] ($prefix) $suffix $field)
+    };
+    (@affix [#![link_suffix = $suffix:literal] $($battr:tt)*]
+        $prefix:tt $old:tt $field:ident
+    ) => {
+        $crate::linker!(@affix [$($battr)*] $prefix ($suffix) $field)
+    };
+    (@affix [#![$($other:tt)*] $($battr:tt)*]
+        $prefix:tt $suffix:tt $field:ident
+    ) => {
+        $crate::linker!(@affix [$($battr)*] $prefix $suffix $field)
+    };
+    (@affix [] ($prefix:literal) ($suffix:literal) $field:ident) => {
+        concat!($prefix, stringify!($field), $suffix)
+    };
+    // Symbol lookup, configured by the item's attributes
+    (@get $ctx:tt ($name:expr) $aliases:tt ($($version:literal)?)
+        ($($null:ident)?) [#[version = $v:literal] $($attr:tt)*]
+    ) => {
+        $crate::linker!(
+            @get $ctx ($name) $aliases ($v) ($($null)?) [$($attr)*]
+        )
+    };
+    (@get $ctx:tt ($name:expr) $old:tt ($($version:literal)?)
+        ($($null:ident)?)
+        [#[aliases($($alias:literal),+ $(,)?)] $($attr:tt)*]
+    ) => {
+        $crate::linker!(@get $ctx ($name) ($($alias),+) ($($version)?)
+            ($($null)?) [$($attr)*]
+        )
+    };
+    (@get $ctx:tt ($name:expr) $aliases:tt ($($version:literal)?)
+        ($($null:ident)?) [#[optional] $($attr:tt)*]
+    ) => {
+        $crate::linker!(
+            @get $ctx ($name) $aliases ($($version)?) (optional) [$($attr)*]
+        )
+    };
+    (@get $ctx:tt ($name:expr) $aliases:tt ($($version:literal)?)
+        (optional) [#[nullable] $($attr:tt)*]
+    ) => {
+        $crate::linker!(
+            @get $ctx ($name) $aliases ($($version)?) (optional) [$($attr)*]
+        )
+    };
+    (@get $ctx:tt ($name:expr) $aliases:tt ($($version:literal)?)
+        ($($null:ident)?) [#[nullable] $($attr:tt)*]
+    ) => {
+        $crate::linker!(
+            @get $ctx ($name) $aliases ($($version)?) (nullable) [$($attr)*]
+        )
+    };
+    (@get $ctx:tt ($name:expr) $aliases:tt ($($version:literal)?)
+        ($($null:ident)?) [#[$($other:tt)*] $($attr:tt)*]
+    ) => {
+        $crate::linker!(
+            @get $ctx ($name) $aliases ($($version)?) ($($null)?) [$($attr)*]
+        )
+    };
+    (@get ($dl_api:ident $names:ident $field:ident) ($name:expr) () ()
+        ($($null:ident)?) []
+    ) => {
+        $crate::linker!(@value ($dl_api $names $field) ($($null)?) [$name] (
+            $dl_api
+                .get($crate::linker!(@cstr $name))
+                .map(|symbol| (0, symbol))
+        ))
+    };
+    (@get ($dl_api:ident $names:ident $field:ident) ($name:expr) ()
+        ($version:literal) ($($null:ident)?) []
+    ) => {
+        $crate::linker!(@value ($dl_api $names $field) ($($null)?)
+            [concat!($name, "@", $version)]
+            ($dl_api
+                .get_versioned(
+                    $crate::linker!(@cstr $name),
+                    $crate::linker!(@cstr $version),
+                )
+                .map(|symbol| (0, symbol)))
+        )
+    };
+    (@get ($dl_api:ident $names:ident $field:ident) ($name:expr)
+        ($($alias:literal),+) () ($($null:ident)?) []
+    ) => {
+        $crate::linker!(@value ($dl_api $names $field) ($($null)?)
+            [$name, $($alias),+]
+            ($dl_api
+                .get_first([
+                    $crate::linker!(@cstr $name),
+                    $($crate::linker!(@cstr $alias)),+
+                ])
+                .map(|found| (found.index, found.symbol)))
+        )
+    };
+    (@get $ctx:tt ($name:expr) ($($alias:literal),+) ($version:literal)
+        ($($null:ident)?) []
+    ) => {
+        compile_error!("`#[aliases]` can't be combined with `#[version]`")
+    };
+    (@value ($dl_api:ident $names:ident $field:ident) (optional)
+        [$($symbol:expr),+] ($lookup:expr)
+    ) => {
+        match $lookup {
+            ::std::result::Result::Ok((
+                index,
+                ::std::option::Option::Some(symbol),
+            )) => {
+                $names.push((stringify!($field), [$($symbol),+][index]));
+                ::std::option::Option::Some(
+                    ::std::mem::transmute(symbol.into_raw())
+                )
+            }
+            _ => ::std::option::Option::None,
+        }
+    };
+    (@value ($dl_api:ident $names:ident $field:ident) (nullable)
+        [$($symbol:expr),+] ($lookup:expr)
+    ) => {{
+        let (index, symbol) = $lookup?;
+        $names.push((stringify!($field), [$($symbol),+][index]));
+        symbol.map(|symbol| ::std::mem::transmute(symbol.into_raw()))
+    }};
+    (@value ($dl_api:ident $names:ident $field:ident) ()
+        [$($symbol:expr),+] ($lookup:expr)
+    ) => {
+        match $lookup? {
+            (index, ::std::option::Option::Some(symbol)) => {
+                $names.push((stringify!($field), [$($symbol),+][index]));
+                ::std::mem::transmute(symbol.into_raw())
+            }
+            (index, ::std::option::Option::None) => {
+                return ::std::result::Result::Err($crate::Error::NullSymbol {
+                    library: $dl_api.name().to_string(),
+                    symbol: [$($symbol),+][index].to_string(),
+                });
+            }
+        }
+    };
+    // Symbol names are checked for NUL characters at compile time
+    (@cstr $string:expr) => {{
+        const NAME: &::std::ffi::CStr = match ::std::ffi::CStr
+            ::from_bytes_with_nul(concat!($string, "\0").as_bytes())
+        {
+            ::std::result::Result::Ok(name) => name,
+            ::std::result::Result::Err(_) => {
+                panic!("Symbol names can't contain NUL characters")
+            }
+        };
+        NAME
+    }};
 );
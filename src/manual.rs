@@ -7,24 +7,1535 @@
 // or http://opensource.org/licenses/Zlib>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::ffi::CStr;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::marker::PhantomData;
+use std::ops::Deref;
 use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError, Weak};
+use std::time::{Duration, SystemTime};
 
+use crate::error::LoaderError;
 use crate::ffi::DlApi as DlApiNative;
+use crate::Error;
+
+// Libraries opened with `DlApi::shared()`, by each name they were opened with.
+static SHARED: Mutex<Vec<(String, Weak<DlApi>)>> = Mutex::new(Vec::new());
+
+// Values created with `shared_instance()`, by type.
+static INSTANCES: Mutex<Vec<(TypeId, Weak<dyn Any + Send + Sync>)>> =
+    Mutex::new(Vec::new());
+
+// ID for the next `ThreadLocal`.
+static NEXT_THREAD_LOCAL: AtomicU64 = AtomicU64::new(0);
+
+// Number for the next copy of a library loaded by `Reloadable`.
+static NEXT_COPY: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // Address of each `ThreadLocal` variable on this thread, by its ID, and
+    // whether the `ThreadLocal` still exists.
+    static THREAD_LOCALS: RefCell<ThreadLocals> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+type ThreadLocals = BTreeMap<u64, (Weak<()>, NonNull<c_void>)>;
 
 /// Dynamically loaded library API.
+///
+/// The library is unloaded when this is dropped (unless it's a pseudo-handle),
+/// or later if there are still [`Lease`]s on it.
 #[derive(Debug)]
-pub struct DlApi(DlApiNative);
+pub struct DlApi {
+    native: Arc<Native>,
+    // The name or path that the library was requested with.
+    name: String,
+}
+
+/// Native handle, which leases share to keep the library loaded.
+#[derive(Debug)]
+struct Native {
+    handle: DlApiNative,
+    // Memory file that the library was loaded from, if any.
+    file: Option<crate::ffi::LibraryFile>,
+    // Number of leases, and a signal (only locked once the number drops to
+    // zero, or by a thread waiting for it to).
+    leases: AtomicUsize,
+    lock: Mutex<()>,
+    released: Condvar,
+}
 
 impl DlApi {
-    /// Load a Dynamic Library API
-    pub fn new(filename: &CStr) -> Option<Self> {
-        Some(DlApi(DlApiNative::new(filename)?))
+    /// Load a Dynamic Library API, with the default [`OpenOptions`].
+    pub fn new(filename: &CStr) -> Result<Self, Error> {
+        OpenOptions::new().open_cstr(filename)
+    }
+
+    /// Load a Dynamic Library API from a file name or path, with the default
+    /// [`OpenOptions`].  Accepts anything that can be referenced as a `Path`,
+    /// including `&str`, `&OsStr`, `String`, `OsString` and `PathBuf`.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    ///
+    /// let exe = std::env::current_exe().unwrap();
+    /// let plugin = exe.with_file_name("libplugin.so");
+    /// let dl_api = DlApi::open(plugin).unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        OpenOptions::new().open(path)
+    }
+
+    /// Get a shared handle to a library, which is only loaded the first time
+    /// (with the default [`OpenOptions`]).  Names that load the same library
+    /// share a handle, and it's unloaded when the last one is dropped.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::sync::Arc;
+    ///
+    /// let a = DlApi::shared("libfoo.so.1").unwrap();
+    /// let b = DlApi::shared("libfoo.so.1").unwrap();
+    /// assert!(Arc::ptr_eq(&a, &b));
+    /// ```
+    pub fn shared<P: AsRef<Path>>(path: P) -> Result<Arc<Self>, Error> {
+        let path = path.as_ref();
+        let name = path.to_string_lossy().into_owned();
+        if let Some(dl_api) = Self::find_shared(&name) {
+            return Ok(dl_api);
+        }
+        // Not locked while loading, which runs the library's constructors
+        // (that might get a shared handle themselves).
+        let dl_api = Self::open(path)?;
+        let mut shared = SHARED.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.retain(|(_, dl_api)| dl_api.strong_count() != 0);
+        // Another thread might have shared the library while it was loading.
+        // Dropping the new handle doesn't unload the library that's shared.
+        let found = shared
+            .iter()
+            .filter_map(|(_, other)| other.upgrade())
+            .find(|other| other.native.handle.is_same(&dl_api.native.handle));
+        let dl_api = found.unwrap_or_else(|| Arc::new(dl_api));
+        shared.push((name, Arc::downgrade(&dl_api)));
+        Ok(dl_api)
+    }
+
+    /// Find a shared handle by the name it was opened with.
+    fn find_shared(name: &str) -> Option<Arc<Self>> {
+        let mut shared = SHARED.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.retain(|(_, dl_api)| dl_api.strong_count() != 0);
+        shared
+            .iter()
+            .find(|(other, _)| other == name)
+            .and_then(|(_, dl_api)| dl_api.upgrade())
+    }
+
+    /// Load a Dynamic Library API from the bytes of a library file, with the
+    /// default [`OpenOptions`] (see [`OpenOptions::open_bytes()`]).
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    ///
+    /// # static PLUGIN: &[u8] = &[];
+    /// // static PLUGIN: &[u8] = include_bytes!("libplugin.so");
+    /// let plugin = DlApi::from_bytes(PLUGIN).unwrap();
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        OpenOptions::new().open_bytes(bytes)
+    }
+
+    /// Get a handle to a library only if it's already loaded, without loading
+    /// it or running its constructors (`RTLD_NOLOAD`).  Always returns `None`
+    /// on platforms that don't support `RTLD_NOLOAD`.
+    ///
+    /// The library stays loaded at least until the handle is dropped.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    ///
+    /// if let Some(vulkan) = DlApi::get_if_loaded("libvulkan.so.1") {
+    ///     println!("Vulkan loaded from {}", vulkan.name());
+    /// }
+    /// ```
+    pub fn get_if_loaded<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let filename = crate::ffi::path_to_cstring(path.as_ref()).ok()?;
+        let native = DlApiNative::get_if_loaded(&filename)?;
+        let name = filename.to_string_lossy().into_owned();
+        Some(DlApi::wrap(native, name))
+    }
+
+    /// Check whether a library is already loaded, without loading it (see
+    /// [`get_if_loaded()`](DlApi::get_if_loaded)).
+    pub fn is_loaded<P: AsRef<Path>>(path: P) -> bool {
+        Self::get_if_loaded(path).is_some()
+    }
+
+    /// Open the main program (`dlopen(NULL)`), to get symbols from the
+    /// executable itself and the libraries it was linked with.  The
+    /// executable's own symbols are only found if it exports them (for
+    /// example, when linked with `-rdynamic`).
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::ffi::CStr;
+    ///
+    /// let process = DlApi::process().unwrap();
+    /// let name = CStr::from_bytes_with_nul(b"optional_feature\0").unwrap();
+    /// let statically_linked = matches!(process.get(name), Ok(Some(_)));
+    /// ```
+    pub fn process() -> Result<Self, Error> {
+        match DlApiNative::process() {
+            Ok(native) => Ok(DlApi::wrap(native, "main program".to_string())),
+            Err(message) => Err(Error::NotInstalled {
+                library: "main program".to_string(),
+                reason: LoaderError::new(message),
+            }),
+        }
+    }
+
+    /// Get the pseudo-handle that searches the global scope (`RTLD_DEFAULT`):
+    /// the main program, its dependencies, and libraries loaded with
+    /// [`OpenOptions::global()`], in load order.
+    pub fn default_scope() -> Self {
+        DlApi::wrap(DlApiNative::default_scope(), "RTLD_DEFAULT".to_string())
+    }
+
+    /// Get the pseudo-handle that searches the libraries loaded after the one
+    /// this crate is linked into (`RTLD_NEXT`), to find the definition that a
+    /// symbol overrides.
+    pub fn next_scope() -> Self {
+        DlApi::wrap(DlApiNative::next_scope(), "RTLD_NEXT".to_string())
+    }
+
+    /// Wrap a native handle.
+    fn wrap(handle: DlApiNative, name: String) -> Self {
+        DlApi {
+            native: Arc::new(Native {
+                handle,
+                file: None,
+                leases: AtomicUsize::new(0),
+                lock: Mutex::new(()),
+                released: Condvar::new(),
+            }),
+            name,
+        }
+    }
+
+    /// Wrap a native handle, or convert the loader's message into an error.
+    fn opened(
+        native: Result<DlApiNative, String>,
+        filename: &CStr,
+    ) -> Result<Self, Error> {
+        let name = filename.to_string_lossy().into_owned();
+        match native {
+            Ok(native) => Ok(DlApi::wrap(native, name)),
+            Err(message) => Err(Error::NotInstalled {
+                library: name,
+                reason: LoaderError::new(message),
+            }),
+        }
+    }
+
+    /// Get the name or path that the library was requested with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get a lease, which keeps the library loaded until it's dropped.
+    pub fn lease(&self) -> Lease {
+        Lease::new(&self.native)
+    }
+
+    /// Attach a lease to an item from the library (like a function pointer),
+    /// so that the library stays loaded for as long as the item is kept.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::ffi::CStr;
+    ///
+    /// let libm = DlApi::open("libm.so.6").unwrap();
+    /// let cos = libm.get(CStr::from_bytes_with_nul(b"cos\0").unwrap());
+    /// type Cos = extern "C" fn(f64) -> f64;
+    /// let cos = unsafe { cos.unwrap().unwrap().cast::<Cos>() };
+    /// let cos = libm.leased(cos.into_raw());
+    /// drop(libm);
+    /// println!("{}", (*cos)(0.0)); // Still loaded
+    /// ```
+    pub fn leased<T>(&self, item: T) -> Leased<T> {
+        Leased {
+            item,
+            lease: self.lease(),
+        }
+    }
+
+    /// Wait until there are no leases on the library.  Waits forever if
+    /// `timeout` is `None`, and returns `false` if there are still leases
+    /// after the timeout.
+    ///
+    /// Only [`Lease`]s are waited for: copies of function pointers that
+    /// weren't [`leased()`](DlApi::leased) don't keep the library loaded.
+    pub fn wait_for_leases(&self, timeout: Option<Duration>) -> bool {
+        let native = &*self.native;
+        let leased = |_: &mut ()| native.leases.load(Ordering::Acquire) != 0;
+        let lock = native.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let _lock = match timeout {
+            Some(timeout) => {
+                native
+                    .released
+                    .wait_timeout_while(lock, timeout, leased)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => native
+                .released
+                .wait_while(lock, leased)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        native.leases.load(Ordering::Acquire) == 0
+    }
+
+    /// Wait until there are no leases on the library, and then drop this
+    /// handle (unloading the library, unless something else has it open).
+    /// Waits forever if `timeout` is `None`, and returns the handle if there
+    /// are still leases after the timeout.
+    ///
+    /// Only [`Lease`]s are waited for: copies of function pointers that
+    /// weren't [`leased()`](DlApi::leased) may dangle after this returns.
+    pub fn unload(self, timeout: Option<Duration>) -> Result<(), Self> {
+        if self.wait_for_leases(timeout) {
+            Ok(())
+        } else {
+            Err(self)
+        }
     }
 
     /// Get a function pointer or pointer to global static from the library.
-    pub fn get(&self, symbol: &CStr) -> Option<NonNull<c_void>> {
-        self.0.get(symbol)
+    /// Returns `Ok(None)` if the symbol exists, but its value is NULL (for
+    /// example, an undefined weak symbol).
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::ffi::CStr;
+    ///
+    /// let libfoo = DlApi::open("libfoo.so").unwrap();
+    /// match libfoo.get(CStr::from_bytes_with_nul(b"foo_hook\0").unwrap()) {
+    ///     Ok(Some(hook)) => println!("Hook at {:p}", *hook),
+    ///     Ok(None) => println!("Hook is NULL"),
+    ///     Err(error) => println!("{}", error),
+    /// }
+    /// ```
+    pub fn get(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<Symbol<'_, NonNull<c_void>>>, Error> {
+        let name = symbol.to_string_lossy();
+        self.symbol(self.native.handle.get(symbol), name.into_owned())
+    }
+
+    /// Get the address of a thread-local variable for the calling thread.  The
+    /// address is only valid on this thread (while it's running), so call this
+    /// again from each thread that uses the variable.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::ffi::CStr;
+    ///
+    /// let libfoo = DlApi::open("libfoo.so").unwrap();
+    /// let name = CStr::from_bytes_with_nul(b"foo_errno\0").unwrap();
+    /// let foo_errno = libfoo.get_tls(name).unwrap().unwrap();
+    /// let foo_errno: *mut i32 = foo_errno.as_ptr().cast();
+    /// println!("foo_errno = {}", unsafe { *foo_errno });
+    /// ```
+    pub fn get_tls(
+        &self,
+        symbol: &CStr,
+    ) -> Result<Option<Symbol<'_, NonNull<c_void>>>, Error> {
+        let name = symbol.to_string_lossy();
+        self.symbol(self.native.handle.get_tls(symbol), name.into_owned())
+    }
+
+    /// Get the first of several names for a function pointer or pointer to
+    /// global static that exists in the library, for symbols that are named
+    /// differently in some versions or builds.  The symbol is `None` if it
+    /// exists, but its value is NULL.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::ffi::CStr;
+    ///
+    /// let libfoo = DlApi::open("libfoo.so").unwrap();
+    /// let names = [
+    ///     CStr::from_bytes_with_nul(b"foo_v2\0").unwrap(),
+    ///     CStr::from_bytes_with_nul(b"foo\0").unwrap(),
+    /// ];
+    /// let found = libfoo.get_first(names).unwrap();
+    /// println!("Found {:?} at {:?}", names[found.index], found.symbol);
+    /// ```
+    pub fn get_first<'a, I>(&self, symbols: I) -> Result<Found<'_>, Error>
+    where
+        I: IntoIterator<Item = &'a CStr>,
+    {
+        let mut skipped = Vec::new();
+        let handle = &self.native.handle;
+        match first_symbol(handle, &self.name, symbols, &mut skipped) {
+            Some((index, pointer)) => Ok(Found {
+                symbol: pointer.map(Symbol::new),
+                index,
+                skipped,
+            }),
+            None => Err(Error::NoneExist { attempts: skipped }),
+        }
+    }
+
+    /// Get a specific version of a function pointer or pointer to global
+    /// static from the library (`dlvsym()`), rather than the default version.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    /// use std::ffi::CStr;
+    ///
+    /// let libc = DlApi::open("libc.so.6").unwrap();
+    /// let memcpy = libc.get_versioned(
+    ///     CStr::from_bytes_with_nul(b"memcpy\0").unwrap(),
+    ///     CStr::from_bytes_with_nul(b"GLIBC_2.2.5\0").unwrap(),
+    /// );
+    /// ```
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub fn get_versioned(
+        &self,
+        symbol: &CStr,
+        version: &CStr,
+    ) -> Result<Option<Symbol<'_, NonNull<c_void>>>, Error> {
+        let name = format!(
+            "{}@{}",
+            symbol.to_string_lossy(),
+            version.to_string_lossy()
+        );
+        self.symbol(self.native.handle.get_versioned(symbol, version), name)
+    }
+
+    /// Get the path of the file that was actually loaded (from the library's
+    /// link map).
+    ///
+    /// ```no_run
+    /// use dl_api::manual::DlApi;
+    ///
+    /// let libfoo = DlApi::open("libfoo.so").unwrap();
+    /// println!("Loaded {}", libfoo.path().unwrap().display());
+    /// ```
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        Ok(self.info(self.native.handle.link_map())?.1)
+    }
+
+    /// Get the base address of the library: the difference between addresses
+    /// in the file and addresses in memory (from the library's link map).
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub fn base_address(&self) -> Result<usize, Error> {
+        Ok(self.info(self.native.handle.link_map())?.0)
+    }
+
+    /// Get the directory that the library was loaded from (what `$ORIGIN`
+    /// expands to in its run path).
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub fn origin(&self) -> Result<PathBuf, Error> {
+        self.info(self.native.handle.origin())
+    }
+
+    /// Get the directories that are searched, in order, when loading the
+    /// library's dependencies.
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub fn search_path(&self) -> Result<Vec<PathBuf>, Error> {
+        self.info(self.native.handle.search_path())
+    }
+
+    /// Convert the loader's message into an error.
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    fn info<T>(&self, info: Result<T, String>) -> Result<T, Error> {
+        info.map_err(|message| Error::InfoUnavailable {
+            library: self.name.clone(),
+            reason: LoaderError::new(message),
+        })
+    }
+
+    /// Wrap a symbol, or convert the loader's message into an error.
+    fn symbol(
+        &self,
+        pointer: Result<Option<NonNull<c_void>>, String>,
+        symbol: String,
+    ) -> Result<Option<Symbol<'_, NonNull<c_void>>>, Error> {
+        match pointer {
+            Ok(pointer) => Ok(pointer.map(Symbol::new)),
+            Err(message) => Err(Error::DoesntExist {
+                library: self.name.clone(),
+                symbol,
+                reason: LoaderError::new(message),
+            }),
+        }
+    }
+}
+
+/// Look up the first of the symbols that exists, and record why each of the
+/// ones before it couldn't be found.
+fn first_symbol<'a, I>(
+    handle: &DlApiNative,
+    library: &str,
+    symbols: I,
+    attempts: &mut Vec<Error>,
+) -> Option<(usize, Option<NonNull<c_void>>)>
+where
+    I: IntoIterator<Item = &'a CStr>,
+{
+    for (index, symbol) in symbols.into_iter().enumerate() {
+        match handle.get(symbol) {
+            Ok(pointer) => return Some((index, pointer)),
+            Err(message) => attempts.push(Error::DoesntExist {
+                library: library.to_string(),
+                symbol: symbol.to_string_lossy().into_owned(),
+                reason: LoaderError::new(message),
+            }),
+        }
+    }
+    None
+}
+
+/// Options for how to load a library (the `dlopen()` mode flags).
+///
+/// Defaults to resolving all symbols immediately, and not making them available
+/// to libraries loaded later.  Flags that the platform doesn't support are
+/// ignored.
+///
+/// ```no_run
+/// use dl_api::manual::OpenOptions;
+///
+/// let dl_api = OpenOptions::new()
+///     .lazy(true)
+///     .global(true)
+///     .open("libvendor.so.1");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    pub(crate) lazy: bool,
+    pub(crate) global: bool,
+    pub(crate) nodelete: bool,
+    pub(crate) noload: bool,
+    pub(crate) deepbind: bool,
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    namespace: Option<Namespace>,
+}
+
+impl OpenOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve function symbols when they're first called (`RTLD_LAZY`),
+    /// rather than when the library is loaded (`RTLD_NOW`).
+    pub fn lazy(&mut self, lazy: bool) -> &mut Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Make the library's symbols available to libraries loaded after it
+    /// (`RTLD_GLOBAL`), rather than keeping them private (`RTLD_LOCAL`).
+    pub fn global(&mut self, global: bool) -> &mut Self {
+        self.global = global;
+        self
+    }
+
+    /// Never unload the library, even after it's dropped (`RTLD_NODELETE`).
+    pub fn nodelete(&mut self, nodelete: bool) -> &mut Self {
+        self.nodelete = nodelete;
+        self
+    }
+
+    /// Fail instead of loading the library if it isn't already loaded
+    /// (`RTLD_NOLOAD`).
+    pub fn noload(&mut self, noload: bool) -> &mut Self {
+        self.noload = noload;
+        self
+    }
+
+    /// Prefer the library's own symbols over global symbols with the same name
+    /// (`RTLD_DEEPBIND`).
+    pub fn deepbind(&mut self, deepbind: bool) -> &mut Self {
+        self.deepbind = deepbind;
+        self
+    }
+
+    /// Load the library into a link-map namespace (`dlmopen()`), instead of
+    /// the namespace of the program.
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    pub fn namespace(&mut self, namespace: &Namespace) -> &mut Self {
+        self.namespace = Some(namespace.clone());
+        self
+    }
+
+    /// Load a Dynamic Library API from a file name or path with these options.
+    ///
+    /// Returns [`Error::NulCharacter`] if the path contains a NUL character.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<DlApi, Error> {
+        let path = path.as_ref();
+        let filename = crate::ffi::path_to_cstring(path).map_err(|error| {
+            Error::NulCharacter {
+                name: path.to_string_lossy().into_owned(),
+                error,
+            }
+        })?;
+        self.open_cstr(&filename)
+    }
+
+    /// Load a library from the bytes of a library file, such as one embedded
+    /// with `include_bytes!()`.  The bytes are written to an anonymous memory
+    /// file on Linux, or to a new temporary file that only the current user
+    /// can access, which is removed once the library is loaded.  A memory
+    /// file stays open until the library is unloaded (so, forever with
+    /// [`nodelete()`](OpenOptions::nodelete)).
+    pub fn open_bytes(&self, bytes: &[u8]) -> Result<DlApi, Error> {
+        let temp_file = |error| Error::TempFile {
+            error: Arc::new(error),
+        };
+        let mut file =
+            crate::ffi::LibraryFile::new(bytes).map_err(temp_file)?;
+        if file.is_memory() {
+            // The loader recognizes libraries by path, so if a library loaded
+            // from a closed memory file with the same path is still loaded, it
+            // would be used instead.
+            let mut noload = self.clone();
+            noload.noload(true);
+            while noload.open_cstr(file.path()).is_ok() {
+                file.renumber().map_err(temp_file)?;
+            }
+        }
+        let mut dl_api = self.open_cstr(file.path())?;
+        // Keep the memory file open until the library is unloaded, so that its
+        // path isn't reused (or forever, if the library is never unloaded).
+        if file.is_memory() {
+            if self.nodelete {
+                std::mem::forget(file);
+            } else if let Some(native) = Arc::get_mut(&mut dl_api.native) {
+                native.file = Some(file);
+            }
+        }
+        Ok(dl_api)
+    }
+
+    /// Try to load each of the candidate file names or paths in order, until
+    /// one succeeds.
+    ///
+    /// Returns [`Error::NoneInstalled`] if none of them could be loaded.
+    ///
+    /// ```no_run
+    /// use dl_api::manual::{versioned, OpenOptions};
+    ///
+    /// let loaded = OpenOptions::new()
+    ///     .open_first(versioned("libfoo.so", &[2, 1]))
+    ///     .unwrap();
+    /// println!("Loaded {}", loaded.dl_api.name());
+    /// for error in loaded.skipped {
+    ///     println!("Skipped: {}", error);
+    /// }
+    /// ```
+    pub fn open_first<I>(&self, candidates: I) -> Result<Loaded, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let mut skipped = Vec::new();
+        for (index, candidate) in candidates.into_iter().enumerate() {
+            match self.open(candidate) {
+                Ok(dl_api) => {
+                    return Ok(Loaded {
+                        dl_api,
+                        index,
+                        skipped,
+                    })
+                }
+                Err(error) => skipped.push(error),
+            }
+        }
+        Err(Error::NoneInstalled { attempts: skipped })
+    }
+
+    fn open_cstr(&self, filename: &CStr) -> Result<DlApi, Error> {
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        {
+            if let Some(namespace) = &self.namespace {
+                return namespace.open(filename, self);
+            }
+        }
+        DlApi::opened(DlApiNative::new(filename, self), filename)
+    }
+}
+
+/// Get the shared instance of a type, or create it if there isn't one.  It's
+/// dropped when the last `Arc` is dropped.  Used by the `shared()` constructor
+/// of structs generated by [`linker!`](crate::linker).
+pub fn shared_instance<T, F>(new: F) -> Result<Arc<T>, Error>
+where
+    T: Any + Send + Sync,
+    F: FnOnce() -> Result<T, Error>,
+{
+    let type_id = TypeId::of::<T>();
+    let find = |instances: &mut Vec<(TypeId, Weak<dyn Any + Send + Sync>)>| {
+        instances.retain(|(_, instance)| instance.strong_count() != 0);
+        let found = instances.iter().find(|(other, _)| *other == type_id);
+        found
+            .and_then(|(_, instance)| instance.upgrade())
+            .and_then(|instance| instance.downcast().ok())
+    };
+    let mut instances =
+        INSTANCES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(instance) = find(&mut instances) {
+        return Ok(instance);
+    }
+    // Not locked while creating it, which might load a library (running its
+    // constructors), or get another shared instance.
+    drop(instances);
+    let instance = Arc::new(new()?);
+    let mut instances =
+        INSTANCES.lock().unwrap_or_else(PoisonError::into_inner);
+    // Another thread might have created one in the meantime, so keep that one.
+    if let Some(instance) = find(&mut instances) {
+        return Ok(instance);
+    }
+    let weak: Weak<T> = Arc::downgrade(&instance);
+    instances.push((type_id, weak));
+    Ok(instance)
+}
+
+/// Structs generated by [`linker!`](crate::linker), which get their items from
+/// a loaded library.
+pub trait Linker: Sized {
+    /// Get the items from a loaded library, which is kept loaded for as long
+    /// as they are.
+    fn from_handle(dl_api: DlApi) -> Result<Self, Error>;
+
+    /// Get the library that the items are from.
+    fn dl_api(&self) -> &DlApi;
+}
+
+/// A struct generated by [`linker!`](crate::linker) for a library that can be
+/// rebuilt while the program is running, and reloaded.
+///
+/// Each reload loads a new copy of the file (so the dynamic loader doesn't
+/// return the old one), gets all of its items, and then replaces the current
+/// struct.  Each copy stays loaded until the last `Arc` from
+/// [`get()`](Reloadable::get) is dropped, so calls into it can finish.
+///
+/// The copies are hidden files in the same directory as the library, so
+/// `$ORIGIN` in its run path still finds the libraries next to it.  The
+/// directory must be writable, and each copy is deleted once it's loaded.
+///
+/// ```no_run
+/// use dl_api::manual::Reloadable;
+///
+/// dl_api::linker!(extern "C" GameLogic "libgame.so" {
+///     fn game_update(dt: f32) -> ();
+/// });
+///
+/// let game = Reloadable::<GameLogic>::new("target/debug/libgame.so").unwrap();
+/// loop {
+///     if game.reload_if_changed().unwrap() {
+///         println!("Reloaded (generation {})", game.generation());
+///     }
+///     let logic = game.get();
+///     unsafe { (logic.game_update)(1.0 / 60.0) };
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Reloadable<T> {
+    path: PathBuf,
+    // When the file was last loaded, also held while reloading.
+    loaded: Mutex<Option<(SystemTime, u64)>>,
+    // The current struct, and its generation.
+    current: Mutex<(Arc<T>, u64)>,
+}
+
+impl<T: Linker> Reloadable<T> {
+    /// Load a copy of the library at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let (api, loaded) = Self::load(&path)?;
+        Ok(Reloadable {
+            path,
+            loaded: Mutex::new(loaded),
+            current: Mutex::new((Arc::new(api), 0)),
+        })
+    }
+
+    /// Get the path of the library file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the current struct.  Keep the `Arc` for as long as calls into the
+    /// library are in progress.
+    pub fn get(&self) -> Arc<T> {
+        let current =
+            self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&current.0)
+    }
+
+    /// Get the number of times the library has been reloaded.
+    pub fn generation(&self) -> u64 {
+        let current =
+            self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        current.1
+    }
+
+    /// Reload the library if the file's modification time or size has
+    /// changed since it was last loaded.  Returns whether it was reloaded.
+    pub fn reload_if_changed(&self) -> Result<bool, Error> {
+        let mut loaded =
+            self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        if *loaded == Self::modified(&self.path)? {
+            return Ok(false);
+        }
+        self.replace(&mut loaded)?;
+        Ok(true)
+    }
+
+    /// Reload the library, returning the new generation.  If loading fails,
+    /// the current struct is kept.
+    pub fn reload(&self) -> Result<u64, Error> {
+        let mut loaded =
+            self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        self.replace(&mut loaded)
+    }
+
+    /// Load a new copy, and replace the current struct with it.
+    fn replace(
+        &self,
+        loaded: &mut Option<(SystemTime, u64)>,
+    ) -> Result<u64, Error> {
+        let (api, modified) = Self::load(&self.path)?;
+        *loaded = modified;
+        let mut current =
+            self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        current.0 = Arc::new(api);
+        current.1 += 1;
+        Ok(current.1)
+    }
+
+    /// Load a new copy of the library, and get its items.
+    fn load(path: &Path) -> Result<(T, Option<(SystemTime, u64)>), Error> {
+        // Checked first, so changes while reading are loaded next time.
+        let modified = Self::modified(path)?;
+        let name = path.to_string_lossy().into_owned();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let copy = path.with_file_name(format!(
+            ".{}.{}.{}",
+            file_name,
+            std::process::id(),
+            NEXT_COPY.fetch_add(1, Ordering::Relaxed),
+        ));
+        if let Err(error) = std::fs::copy(path, &copy) {
+            return Err(Error::TempFile {
+                error: Arc::new(error),
+            });
+        }
+        let dl_api = OpenOptions::new().open(&copy);
+        // The copy stays mapped until it's unloaded.
+        let _ = std::fs::remove_file(&copy);
+        let mut dl_api = match dl_api {
+            Ok(dl_api) => dl_api,
+            Err(Error::NotInstalled { reason, .. }) => {
+                return Err(Error::NotInstalled {
+                    library: name,
+                    reason,
+                })
+            }
+            Err(error) => return Err(error),
+        };
+        dl_api.name = name;
+        Ok((T::from_handle(dl_api)?, modified))
+    }
+
+    /// Get the modification time and size of the library file.
+    fn modified(path: &Path) -> Result<Option<(SystemTime, u64)>, Error> {
+        let metadata =
+            std::fs::metadata(path).map_err(|error| Error::ReadFailed {
+                library: path.to_string_lossy().into_owned(),
+                error: Arc::new(error),
+            })?;
+        Ok(metadata.modified().ok().map(|time| (time, metadata.len())))
+    }
+}
+
+/// A library loaded from a list of candidates by [`OpenOptions::open_first()`].
+#[derive(Debug)]
+pub struct Loaded {
+    /// The library that was loaded.
+    pub dl_api: DlApi,
+    /// Index of the candidate that was loaded.
+    pub index: usize,
+    /// Why each of the candidates before it failed to load.
+    pub skipped: Vec<Error>,
+}
+
+/// A symbol found from a list of names by [`DlApi::get_first()`].
+#[derive(Debug)]
+pub struct Found<'lib> {
+    /// The symbol that was found, or `None` if it's NULL.
+    pub symbol: Option<Symbol<'lib, NonNull<c_void>>>,
+    /// Index of the name that was found.
+    pub index: usize,
+    /// Why each of the names before it couldn't be found.
+    pub skipped: Vec<Error>,
+}
+
+/// Get the candidate file names for a library with a base name like
+/// `libfoo.so`, and a list of acceptable major versions, in order of
+/// preference.  The unversioned base name is the last candidate.
+///
+/// ```
+/// use dl_api::manual::versioned;
+///
+/// assert_eq!(
+///     versioned("libfoo.so", &[2, 1]),
+///     ["libfoo.so.2", "libfoo.so.1", "libfoo.so"],
+/// );
+/// ```
+pub fn versioned<S: AsRef<OsStr>>(base: S, versions: &[u32]) -> Vec<OsString> {
+    let base = base.as_ref();
+    let mut candidates = Vec::with_capacity(versions.len() + 1);
+    for version in versions {
+        let mut candidate = base.to_os_string();
+        candidate.push(format!(".{}", version));
+        candidates.push(candidate);
+    }
+    candidates.push(base.to_os_string());
+    candidates
+}
+
+/// A logical library name and major version, that resolves to the file names
+/// that the library would have on each platform.
+///
+/// ```
+/// use dl_api::manual::LibraryName;
+///
+/// let mut name = LibraryName::new("mylibrary");
+/// name.version(1).target("macos", "libMyLibrary.dylib");
+///
+/// assert_eq!(
+///     name.candidates_for("linux"),
+///     ["libmylibrary.so.1"],
+/// );
+/// assert_eq!(
+///     name.candidates_for("windows"),
+///     ["mylibrary-1.dll", "mylibrary1.dll"],
+/// );
+/// assert_eq!(name.candidates_for("macos"), ["libMyLibrary.dylib"]);
+///
+/// let name = LibraryName::new("mylibrary");
+/// assert_eq!(name.candidates_for("linux"), ["libmylibrary.so"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LibraryName {
+    name: String,
+    version: Option<u32>,
+    overrides: Vec<(String, OsString)>,
+}
+
+impl LibraryName {
+    /// Create a library name without a `lib` prefix or file extension (the
+    /// `lib` prefix is allowed, though).
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        LibraryName {
+            name: name.into(),
+            version: None,
+            overrides: Vec::new(),
+        }
     }
+
+    /// Set the major version of the library.  The unversioned file name isn't
+    /// tried then, since it might be a different major version (on Linux,
+    /// `libfoo.so` is usually a symbolic link for development to whichever
+    /// version is installed).
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Use `file_name` instead of the usual file names on an operating system,
+    /// as named by `std::env::consts::OS`.  If called more than once for the
+    /// same operating system, each file name is tried in order.
+    pub fn target<S, P>(&mut self, os: S, file_name: P) -> &mut Self
+    where
+        S: Into<String>,
+        P: AsRef<OsStr>,
+    {
+        self.overrides
+            .push((os.into(), file_name.as_ref().to_os_string()));
+        self
+    }
+
+    /// Get the file names to try for the current operating system, in order of
+    /// preference.
+    pub fn candidates(&self) -> Vec<OsString> {
+        self.candidates_for(std::env::consts::OS)
+    }
+
+    /// Get the file names to try for an operating system, as named by
+    /// `std::env::consts::OS`, in order of preference.
+    pub fn candidates_for(&self, os: &str) -> Vec<OsString> {
+        let overrides: Vec<OsString> = self
+            .overrides
+            .iter()
+            .filter(|(target, _)| target == os)
+            .map(|(_, file_name)| file_name.clone())
+            .collect();
+        if !overrides.is_empty() {
+            return overrides;
+        }
+
+        let name = &self.name;
+        let lib = if name.starts_with("lib") { "" } else { "lib" };
+        let mut candidates = Vec::new();
+        match os {
+            "windows" => {
+                if let Some(version) = self.version {
+                    candidates.push(format!("{}-{}.dll", name, version));
+                    candidates.push(format!("{}{}.dll", name, version));
+                } else {
+                    candidates.push(format!("{}.dll", name));
+                }
+            }
+            "macos" | "ios" => {
+                if let Some(version) = self.version {
+                    candidates
+                        .push(format!("{}{}.{}.dylib", lib, name, version));
+                } else {
+                    candidates.push(format!("{}{}.dylib", lib, name));
+                }
+            }
+            // Android libraries aren't versioned.
+            "android" => candidates.push(format!("{}{}.so", lib, name)),
+            _ => {
+                if let Some(version) = self.version {
+                    candidates.push(format!("{}{}.so.{}", lib, name, version));
+                } else {
+                    candidates.push(format!("{}{}.so", lib, name));
+                }
+            }
+        }
+        candidates.into_iter().map(OsString::from).collect()
+    }
+}
+
+/// A link-map namespace, which keeps its own copy of every library loaded into
+/// it (including dependencies), along with the library's global state.
+///
+/// Clones of a `Namespace` refer to the same namespace.  glibc supports a
+/// maximum of 16 namespaces per process, and doesn't allow loading libraries
+/// into a namespace with [`OpenOptions::global()`].
+///
+/// ```no_run
+/// use dl_api::manual::{Namespace, OpenOptions};
+///
+/// // Two copies of the same library, with independent globals.
+/// let a = OpenOptions::new().namespace(&Namespace::new()).open("libstate.so");
+/// let b = OpenOptions::new().namespace(&Namespace::new()).open("libstate.so");
+/// ```
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[derive(Debug, Clone)]
+pub struct Namespace(Arc<Mutex<Option<crate::ffi::Lmid>>>);
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+impl Namespace {
+    /// Create a new namespace.  The namespace is created when the first
+    /// library is loaded into it.
+    pub fn new() -> Self {
+        Namespace(Arc::new(Mutex::new(None)))
+    }
+
+    /// The namespace that the program itself was loaded into.
+    pub fn base() -> Self {
+        Namespace(Arc::new(Mutex::new(Some(crate::ffi::LM_ID_BASE))))
+    }
+
+    fn open(
+        &self,
+        filename: &CStr,
+        options: &OpenOptions,
+    ) -> Result<DlApi, Error> {
+        // Locked until loaded, so that a new namespace is only created once.
+        let mut lmid = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let dl_api = DlApiNative::new_in(*lmid, filename, options);
+        if let (None, Ok(dl_api)) = (*lmid, &dl_api) {
+            *lmid = dl_api.namespace();
+        }
+        DlApi::opened(dl_api, filename)
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+impl Default for Namespace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps a library loaded while it exists, even after its [`DlApi`] is
+/// dropped.  Get one with [`DlApi::lease()`].
+#[derive(Debug)]
+pub struct Lease {
+    native: Arc<Native>,
+}
+
+impl Lease {
+    fn new(native: &Arc<Native>) -> Self {
+        native.leases.fetch_add(1, Ordering::Relaxed);
+        Lease {
+            native: Arc::clone(native),
+        }
+    }
+}
+
+impl Clone for Lease {
+    fn clone(&self) -> Self {
+        Lease::new(&self.native)
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let native = &*self.native;
+        if native.leases.fetch_sub(1, Ordering::Release) == 1 {
+            // Lock so that a waiting thread can't miss the signal between
+            // checking the count and waiting.
+            let _lock =
+                native.lock.lock().unwrap_or_else(PoisonError::into_inner);
+            native.released.notify_all();
+        }
+    }
+}
+
+/// An item from a library (like a function pointer), with a [`Lease`] that
+/// keeps the library loaded for as long as the item is kept.  Get one with
+/// [`DlApi::leased()`].
+#[derive(Debug, Clone)]
+pub struct Leased<T> {
+    item: T,
+    lease: Lease,
+}
+
+impl<T> Leased<T> {
+    /// Get the lease on the library.
+    pub fn lease(&self) -> &Lease {
+        &self.lease
+    }
+}
+
+impl<T> Deref for Leased<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+/// A function pointer or pointer to global static borrowed from a [`DlApi`].
+///
+/// The borrow prevents using the symbol after the library is unloaded.
+#[derive(Debug, Copy, Clone)]
+pub struct Symbol<'lib, T> {
+    item: T,
+    lib: PhantomData<&'lib DlApi>,
+}
+
+impl<T> Symbol<'_, T> {
+    fn new(item: T) -> Self {
+        Symbol {
+            item,
+            lib: PhantomData,
+        }
+    }
+
+    /// Get the raw value, detached from the lifetime of the library.  It's up
+    /// to the caller to make sure it's not used after the library is dropped.
+    pub fn into_raw(self) -> T {
+        self.item
+    }
+}
+
+impl<'lib> Symbol<'lib, NonNull<c_void>> {
+    /// Reinterpret the symbol as a function pointer or other pointer type.
+    ///
+    /// # Safety
+    /// `T` must be pointer-sized, and must match the actual type of the symbol.
+    #[allow(unsafe_code)]
+    pub unsafe fn cast<T: Copy>(self) -> Symbol<'lib, T> {
+        assert_eq!(size_of::<T>(), size_of::<NonNull<c_void>>());
+        Symbol::new(std::mem::transmute_copy(&self.item))
+    }
+}
+
+impl<T> Deref for Symbol<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+/// A function pointer or pointer to global static in a library, which is looked
+/// up the first time it's used.  Used for lazy functions in
+/// [`linker!`](crate::linker).
+///
+/// Dereferencing it panics if the symbol can't be found (or is NULL), and
+/// [`get()`](Lazy::get) returns an error instead.
+#[derive(Debug)]
+pub struct Lazy<T> {
+    native: Arc<Native>,
+    library: String,
+    // Names to try, in order.
+    symbols: Vec<CString>,
+    // The item, and the index of the name it was found by.
+    item: OnceLock<(T, usize)>,
+}
+
+impl<T: Copy> Lazy<T> {
+    /// Prepare to look up a symbol when it's first used.
+    ///
+    /// # Safety
+    /// `T` must be pointer-sized, and must match the actual type of the symbol.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(dl_api: &DlApi, symbol: &CStr) -> Self {
+        Self::first(dl_api, &[symbol])
+    }
+
+    /// Prepare to look up the first of several names for a symbol that
+    /// exists, when it's first used.
+    ///
+    /// # Safety
+    /// `T` must be pointer-sized, and must match the actual type of the symbol
+    /// (by any of its names).
+    #[allow(unsafe_code)]
+    pub unsafe fn first(dl_api: &DlApi, symbols: &[&CStr]) -> Self {
+        assert_eq!(size_of::<T>(), size_of::<NonNull<c_void>>());
+        Lazy {
+            native: Arc::clone(&dl_api.native),
+            library: dl_api.name.clone(),
+            symbols: symbols
+                .iter()
+                .map(|symbol| (*symbol).to_owned())
+                .collect(),
+            item: OnceLock::new(),
+        }
+    }
+
+    /// Get the item, looking it up if this is the first time.
+    #[allow(unsafe_code)]
+    pub fn get(&self) -> Result<T, Error> {
+        if let Some((item, _)) = self.item.get() {
+            return Ok(*item);
+        }
+        let symbols = self.symbols.iter().map(CString::as_c_str);
+        let mut attempts = Vec::new();
+        let found = first_symbol(
+            &self.native.handle,
+            &self.library,
+            symbols,
+            &mut attempts,
+        );
+        let (index, pointer) = match found {
+            Some((index, Some(pointer))) => (index, pointer),
+            Some((index, None)) => {
+                return Err(Error::NullSymbol {
+                    library: self.library.clone(),
+                    symbol: self.symbols[index].to_string_lossy().into_owned(),
+                })
+            }
+            None if attempts.len() == 1 => return Err(attempts.remove(0)),
+            None => return Err(Error::NoneExist { attempts }),
+        };
+        // Size was checked by `first()`, and the type is up to its caller.
+        let item = unsafe { std::mem::transmute_copy(&pointer) };
+        Ok(self.item.get_or_init(|| (item, index)).0)
+    }
+
+    /// Get the item if it's already been looked up.
+    pub fn resolved(&self) -> Option<T> {
+        self.item.get().map(|(item, _)| *item)
+    }
+
+    /// Get the name that the item was found by, if it's already been looked
+    /// up.
+    pub fn symbol_name(&self) -> Option<&CStr> {
+        let (_, index) = self.item.get()?;
+        Some(&self.symbols[*index])
+    }
+}
+
+impl<T: Copy> Deref for Lazy<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        if let Err(error) = self.get() {
+            panic!("{}", error);
+        }
+        // Set by `get()`.
+        &self.item.get().unwrap().0
+    }
+}
+
+/// A thread-local variable in a library, which has a different address on each
+/// thread.  Used for `thread_local static` items in
+/// [`linker!`](crate::linker).
+///
+/// The library stays loaded for as long as this exists.
+#[derive(Debug)]
+pub struct ThreadLocal<T> {
+    native: Arc<Native>,
+    symbol: CString,
+    // Key for the cached address on each thread, and whether it's still used.
+    id: u64,
+    alive: Arc<()>,
+    variable: PhantomData<fn() -> *mut T>,
+}
+
+impl<T> ThreadLocal<T> {
+    /// Look up a thread-local variable, checking that it exists.
+    ///
+    /// # Safety
+    /// `T` must match the actual type of the variable.
+    #[allow(unsafe_code)]
+    pub unsafe fn new(dl_api: &DlApi, symbol: &CStr) -> Result<Self, Error> {
+        if dl_api.get_tls(symbol)?.is_none() {
+            return Err(Error::NullSymbol {
+                library: dl_api.name.clone(),
+                symbol: symbol.to_string_lossy().into_owned(),
+            });
+        }
+        Ok(ThreadLocal {
+            native: Arc::clone(&dl_api.native),
+            symbol: symbol.to_owned(),
+            id: NEXT_THREAD_LOCAL.fetch_add(1, Ordering::Relaxed),
+            alive: Arc::new(()),
+            variable: PhantomData,
+        })
+    }
+
+    /// Get the address of the variable for the calling thread, which is only
+    /// valid on this thread.  Returns NULL if it couldn't be allocated.
+    ///
+    /// The dynamic loader is only asked the first time on each thread, and
+    /// the address is cached after that.
+    pub fn get(&self) -> *mut T {
+        let cached = THREAD_LOCALS.with(|addresses| {
+            addresses
+                .borrow()
+                .get(&self.id)
+                .map(|(_, address)| *address)
+        });
+        if let Some(address) = cached {
+            return address.cast().as_ptr();
+        }
+        match self.native.handle.get_tls(&self.symbol) {
+            Ok(Some(address)) => {
+                THREAD_LOCALS.with(|addresses| {
+                    let mut addresses = addresses.borrow_mut();
+                    // Forget the variables that have been dropped (from any
+                    // thread), so this only grows with ones that still exist.
+                    addresses.retain(|_, (alive, _)| alive.strong_count() != 0);
+                    let alive = Arc::downgrade(&self.alive);
+                    addresses.insert(self.id, (alive, address));
+                });
+                address.cast().as_ptr()
+            }
+            _ => std::ptr::null_mut(),
+        }
+    }
+}
+
+/// Information about the library and symbol containing an address, found with
+/// `dladdr()`.
+///
+/// ```no_run
+/// use dl_api::manual::{DlApi, SymbolInfo};
+/// use std::ffi::CStr;
+///
+/// let libm = DlApi::open("libm.so.6").unwrap();
+/// let cos = libm.get(CStr::from_bytes_with_nul(b"cos\0").unwrap());
+/// let cos = cos.unwrap().unwrap();
+/// let info = SymbolInfo::new(cos.as_ptr()).unwrap();
+/// println!("{}", info); // cos+0x0 in /lib/x86_64-linux-gnu/libm.so.6
+/// ```
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub(crate) address: usize,
+    pub(crate) path: PathBuf,
+    pub(crate) base: usize,
+    pub(crate) symbol: Option<String>,
+    pub(crate) symbol_address: Option<usize>,
+    pub(crate) symbol_size: Option<usize>,
+}
+
+impl SymbolInfo {
+    /// Look up the library and symbol containing an address.  Returns `None`
+    /// if the address isn't in any loaded library.
+    pub fn new(address: *const c_void) -> Option<Self> {
+        crate::ffi::symbol_info(address)
+    }
+
+    /// Get the address that was looked up.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Get the path of the library containing the address.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the address that the library was loaded at.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Get the name of the nearest symbol at or before the address, if any.
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    /// Get the address of the nearest symbol at or before the address, if any.
+    pub fn symbol_address(&self) -> Option<usize> {
+        self.symbol_address
+    }
+
+    /// Get the size of the nearest symbol in bytes, if known (only on Linux
+    /// with glibc).
+    pub fn symbol_size(&self) -> Option<usize> {
+        self.symbol_size
+    }
+}
+
+impl std::fmt::Display for SymbolInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.symbol, self.symbol_address) {
+            (Some(symbol), Some(symbol_address)) => {
+                let offset = self.address.wrapping_sub(symbol_address);
+                write!(f, "{}+{:#x}", symbol, offset)?;
+            }
+            _ => write!(f, "{:#x}", self.address.wrapping_sub(self.base))?,
+        }
+        write!(f, " in {}", self.path.display())
+    }
+}
+
+/// A shared object (or the executable) loaded in the process, found with
+/// `dl_iterate_phdr()`.
+#[derive(Debug, Clone)]
+pub struct SharedObject {
+    pub(crate) path: PathBuf,
+    pub(crate) base: usize,
+    pub(crate) program_headers: Vec<ProgramHeader>,
+    pub(crate) tls_module: Option<usize>,
+}
+
+impl SharedObject {
+    /// Get the path of the object, as the dynamic loader knows it.  This is
+    /// empty for the executable itself.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the base address of the object: the difference between addresses
+    /// in the file and addresses in memory.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Get the object's ELF program headers.
+    pub fn program_headers(&self) -> &[ProgramHeader] {
+        &self.program_headers
+    }
+
+    /// Get the object's thread-local storage module ID, if it has any
+    /// thread-local storage (and the system reports it).
+    pub fn tls_module(&self) -> Option<usize> {
+        self.tls_module
+    }
+}
+
+/// An ELF program header of a loaded object, which describes one of its
+/// segments.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramHeader {
+    pub(crate) kind: u32,
+    pub(crate) flags: u32,
+    pub(crate) offset: usize,
+    pub(crate) virtual_address: usize,
+    pub(crate) file_size: usize,
+    pub(crate) memory_size: usize,
+    pub(crate) alignment: usize,
+}
+
+impl ProgramHeader {
+    /// Get the segment type (`p_type`, for example `PT_LOAD` = 1).
+    pub fn kind(&self) -> u32 {
+        self.kind
+    }
+
+    /// Get the segment permissions (`p_flags`: 1 = execute, 2 = write,
+    /// 4 = read).
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Get the offset of the segment in the file.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Get the address of the segment relative to the object's base address.
+    pub fn virtual_address(&self) -> usize {
+        self.virtual_address
+    }
+
+    /// Get the size of the segment in the file.
+    pub fn file_size(&self) -> usize {
+        self.file_size
+    }
+
+    /// Get the size of the segment in memory.
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    /// Get the alignment of the segment.
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+}
+
+/// List the shared objects loaded in the process, including the executable
+/// and ones that weren't loaded by this crate, with `dl_iterate_phdr()`.
+///
+/// ```no_run
+/// use dl_api::manual::loaded_libraries;
+///
+/// let loaded = loaded_libraries().any(|object| {
+///     object.path().file_name() == Some("libfoo.so.1".as_ref())
+/// });
+/// ```
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "fuchsia"
+))]
+pub fn loaded_libraries() -> impl Iterator<Item = SharedObject> {
+    crate::ffi::loaded_libraries().into_iter()
 }
@@ -0,0 +1,61 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+mod common;
+
+use dl_api::manual::{DlApi, OpenOptions};
+use std::ffi::CStr;
+
+fn cos() -> &'static CStr {
+    CStr::from_bytes_with_nul(b"cos\0").unwrap()
+}
+
+fn bytes(name: &str) -> Vec<u8> {
+    std::fs::read(common::library_path(name)).unwrap()
+}
+
+#[test]
+fn from_bytes_loads_library() {
+    let libm = DlApi::from_bytes(&bytes("libm.so.6")).unwrap();
+    let cos = libm.get(cos()).unwrap().unwrap();
+    type Cos = extern "C" fn(f64) -> f64;
+    assert_eq!(unsafe { cos.cast::<Cos>() }.into_raw()(0.0), 1.0);
+}
+
+// The loader finds libraries by path, and a memory file's path is its file
+// descriptor, which can be reused once it's closed.
+#[test]
+fn from_bytes_doesnt_reuse_a_loaded_path() {
+    let libutil = bytes("libutil.so.1");
+    let libm = bytes("libm.so.6");
+
+    // Kept loaded after its memory file is closed.
+    let old = DlApi::from_bytes(&libutil).unwrap();
+    let kept = DlApi::get_if_loaded(old.name()).unwrap();
+    assert!(old.get(cos()).is_err());
+    drop(old);
+    let new = DlApi::from_bytes(&libm).unwrap();
+    assert_ne!(new.name(), kept.name());
+    assert!(new.get(cos()).unwrap().is_some());
+    drop(new);
+
+    // Never unloaded.
+    let old = OpenOptions::new()
+        .nodelete(true)
+        .open_bytes(&libutil)
+        .unwrap();
+    let name = old.name().to_string();
+    drop(old);
+    assert!(DlApi::is_loaded(&name));
+    let new = DlApi::from_bytes(&libm).unwrap();
+    assert_ne!(new.name(), name);
+    assert!(new.get(cos()).unwrap().is_some());
+}
@@ -0,0 +1,55 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Helpers shared by the integration tests (not all of them use each one).
+#![allow(dead_code)]
+
+use dl_api::manual::DlApi;
+use std::path::{Path, PathBuf};
+
+/// Find the path of a system library, by loading it.
+pub fn library_path(name: &str) -> PathBuf {
+    DlApi::open(name).unwrap().path().unwrap()
+}
+
+/// A directory in the temporary directory, which is removed when dropped
+/// (even if the test fails).
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a directory for a test.
+    pub fn new(test: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "dl_api-{}-{}",
+            test,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    /// Get the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Copy a system library into the directory, so that nothing else has it
+    /// loaded by that path.
+    pub fn copy_library(&self, name: &str) -> PathBuf {
+        let path = self.0.join(name);
+        std::fs::copy(library_path(name), &path).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
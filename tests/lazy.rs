@@ -0,0 +1,50 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+use dl_api::Error;
+
+dl_api::linker!(extern "C" Libm "libm.so.6" {
+    #![lazy]
+
+    fn cos(x: f64) -> f64;
+    #[aliases("dl_api_missing_sine", "sin")]
+    fn sine(x: f64) -> f64;
+    fn dl_api_missing_function() -> ();
+});
+
+#[test]
+fn lazy_resolves_once() {
+    let libm = Libm::new().unwrap();
+    assert!(libm.cos.resolved().is_none());
+    assert_eq!(libm.symbol_name("cos"), None);
+
+    let cos = libm.cos.get().unwrap();
+    assert_eq!(libm.cos.resolved().map(|f| f as usize), Some(cos as usize));
+    assert_eq!(libm.cos.get().unwrap() as usize, cos as usize);
+    assert_eq!(unsafe { (*libm.cos)(0.0) }, 1.0);
+    assert_eq!(libm.symbol_name("cos"), Some("cos"));
+
+    assert_eq!(unsafe { (*libm.sine)(0.0) }, 0.0);
+    assert_eq!(libm.symbol_name("sine"), Some("sin"));
+}
+
+#[test]
+fn lazy_reports_errors_from_get() {
+    let libm = Libm::new().unwrap();
+    match libm.dl_api_missing_function.get() {
+        Err(Error::DoesntExist { symbol, .. }) => {
+            assert_eq!(symbol, "dl_api_missing_function")
+        }
+        other => panic!("Expected DoesntExist, got {:?}", other.err()),
+    }
+    assert!(libm.dl_api_missing_function.resolved().is_none());
+    assert!(libm.dl_api_missing_function.get().is_err());
+}
@@ -0,0 +1,55 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+mod common;
+
+use common::TempDir;
+use dl_api::manual::DlApi;
+use std::time::Duration;
+
+dl_api::linker!(extern "C" Libm "libm.so.6" {
+    fn cos(x: f64) -> f64;
+});
+
+#[test]
+fn lease_keeps_library_loaded() {
+    let dir = TempDir::new("lease-keep");
+    let path = dir.copy_library("libm.so.6");
+    let libm = DlApi::open(&path).unwrap();
+    let lease = libm.lease();
+    drop(libm);
+    assert!(DlApi::is_loaded(&path));
+    drop(lease);
+    assert!(!DlApi::is_loaded(&path));
+}
+
+#[test]
+fn unload_waits_for_leases() {
+    let dir = TempDir::new("lease-unload");
+    let path = dir.copy_library("libm.so.6");
+    let timeout = Some(Duration::from_millis(10));
+
+    let libm = DlApi::open(&path).unwrap();
+    let lease = libm.lease();
+    let libm = libm.unload(timeout).unwrap_err();
+    let thread = std::thread::spawn(move || drop(lease));
+    libm.unload(None).unwrap();
+    thread.join().unwrap();
+    assert!(!DlApi::is_loaded(&path));
+
+    let libm = Libm::open(&path).unwrap();
+    let cos = libm.leased(|libm| libm.cos);
+    let libm = libm.unload(timeout).unwrap_err();
+    assert_eq!(unsafe { (*cos)(0.0) }, 1.0);
+    drop(cos);
+    libm.unload(timeout).unwrap();
+    assert!(!DlApi::is_loaded(&path));
+}
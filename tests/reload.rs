@@ -0,0 +1,43 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+mod common;
+
+use common::TempDir;
+use dl_api::manual::Reloadable;
+use std::sync::Arc;
+
+dl_api::linker!(extern "C" Libm "libm.so.6" {
+    fn cos(x: f64) -> f64;
+});
+
+#[test]
+fn reload_loads_a_new_copy() {
+    let dir = TempDir::new("reload");
+    let path = dir.copy_library("libm.so.6");
+
+    let reloadable = Reloadable::<Libm>::new(&path).unwrap();
+    let old = reloadable.get();
+    assert_eq!(reloadable.generation(), 0);
+    assert_eq!(old.dl_api().name(), path.to_str().unwrap());
+    assert!(!reloadable.reload_if_changed().unwrap());
+
+    assert_eq!(reloadable.reload().unwrap(), 1);
+    let new = reloadable.get();
+    assert_eq!(reloadable.generation(), 1);
+    assert!(!Arc::ptr_eq(&old, &new));
+    assert_ne!(old.cos as usize, new.cos as usize);
+    assert_eq!(unsafe { (new.cos)(0.0) }, 1.0);
+    // The copy is in the same directory, so `$ORIGIN` is the same.
+    assert_eq!(new.dl_api().origin().unwrap(), dir.path());
+    // Copies are deleted once they're loaded.
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
@@ -0,0 +1,25 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use dl_api::manual::shared_instance;
+use std::sync::Arc;
+
+struct Inner;
+
+struct Outer(Arc<Inner>);
+
+#[test]
+fn shared_instance_can_get_another_while_creating() {
+    let outer =
+        shared_instance(|| Ok(Outer(shared_instance(|| Ok(Inner))?))).unwrap();
+    let inner = shared_instance::<Inner, _>(|| unreachable!()).unwrap();
+    assert!(Arc::ptr_eq(&outer.0, &inner));
+    let same = shared_instance::<Outer, _>(|| unreachable!()).unwrap();
+    assert!(Arc::ptr_eq(&outer, &same));
+}
@@ -0,0 +1,139 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// `GLIBC_PRIVATE` is the name of a symbol version, which glibc exports as an
+// absolute symbol with the value 0.
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+use dl_api::manual::DlApi;
+use dl_api::Error;
+use std::ffi::CStr;
+use std::fs::File;
+use std::os::raw::{c_char, c_int};
+use std::os::unix::io::AsRawFd;
+
+fn cstr(bytes: &[u8]) -> &CStr {
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
+dl_api::linker!(extern "C" Null "libc.so.6" {
+    static GLIBC_PRIVATE: *const u8;
+});
+
+dl_api::linker!(extern "C" Nullable "libc.so.6" {
+    #[nullable]
+    static GLIBC_PRIVATE: *const u8;
+});
+
+dl_api::linker!(extern "C" Missing "libc.so.6" {
+    #[nullable]
+    fn dl_api_missing_function() -> ();
+});
+
+dl_api::linker!(extern "C" Optional "libc.so.6" {
+    fn strlen(s: *const c_char) -> usize;
+    #[optional]
+    fn dl_api_missing_function() -> ();
+    #[optional]
+    static GLIBC_PRIVATE: *const u8;
+});
+
+dl_api::linker!(extern "C" Aliased "libc.so.6" {
+    #[aliases("dl_api_missing", "strlen")]
+    fn dl_api_missing_strlen(s: *const c_char) -> usize;
+});
+
+dl_api::linker!(extern "C" NoAlias "libc.so.6" {
+    #[aliases("dl_api_missing")]
+    fn dl_api_missing_strlen(s: *const c_char) -> usize;
+});
+
+dl_api::linker!(extern "C" Safe "libc.so.6" {
+    #[safe]
+    fn strlen(#[cstr] s: *const c_char) -> usize;
+    #[safe]
+    fn write(fd: c_int, #[slice] buf: *const u8, count: usize) -> isize;
+    #[safe]
+    fn read(fd: c_int, #[slice] buf: *mut u8, count: usize) -> isize;
+});
+
+#[test]
+fn get_distinguishes_null_from_missing() {
+    let libc = DlApi::open("libc.so.6").unwrap();
+    assert!(libc.get(cstr(b"strlen\0")).unwrap().is_some());
+    assert!(libc.get(cstr(b"GLIBC_PRIVATE\0")).unwrap().is_none());
+    match libc.get(cstr(b"dl_api_missing_function\0")) {
+        Err(Error::DoesntExist { symbol, .. }) => {
+            assert_eq!(symbol, "dl_api_missing_function")
+        }
+        other => panic!("Expected DoesntExist, got {:?}", other),
+    }
+}
+
+#[test]
+fn linker_rejects_null_unless_nullable() {
+    match Null::new() {
+        Err(Error::NullSymbol { symbol, .. }) => {
+            assert_eq!(symbol, "GLIBC_PRIVATE")
+        }
+        other => panic!("Expected NullSymbol, got {:?}", other.err()),
+    }
+    assert!(Nullable::new().unwrap().GLIBC_PRIVATE.is_none());
+    match Missing::new() {
+        Err(Error::DoesntExist { .. }) => {}
+        other => panic!("Expected DoesntExist, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn linker_allows_missing_optional_items() {
+    let libc = Optional::new().unwrap();
+    assert!(libc.dl_api_missing_function.is_none());
+    assert!(libc.GLIBC_PRIVATE.is_none());
+    assert_eq!(unsafe { (libc.strlen)(cstr(b"Hi\0").as_ptr()) }, 2);
+    assert_eq!(libc.symbol_name("strlen"), Some("strlen"));
+}
+
+#[test]
+fn safe_methods_convert_parameters() {
+    let libc = Safe::new().unwrap();
+    assert_eq!(libc.strlen(cstr(b"Hello\0")), 5);
+    assert_eq!(libc.strlen(cstr(b"\0")), 0);
+
+    let null = File::create("/dev/null").unwrap();
+    assert_eq!(libc.write(null.as_raw_fd(), b"Hello"), 5);
+    assert_eq!(libc.write(null.as_raw_fd(), &[]), 0);
+
+    let zero = File::open("/dev/zero").unwrap();
+    let mut buf = [1u8; 4];
+    assert_eq!(libc.read(zero.as_raw_fd(), &mut buf[..3]), 3);
+    assert_eq!(buf, [0, 0, 0, 1]);
+}
+
+#[test]
+fn linker_tries_aliases_in_order() {
+    let libc = Aliased::new().unwrap();
+    assert_eq!(libc.symbol_name("dl_api_missing_strlen"), Some("strlen"));
+    let hello = cstr(b"Hello\0");
+    assert_eq!(unsafe { (libc.dl_api_missing_strlen)(hello.as_ptr()) }, 5);
+
+    match NoAlias::new() {
+        Err(Error::NoneExist { attempts }) => {
+            let symbols: Vec<_> = attempts
+                .iter()
+                .map(|attempt| match attempt {
+                    Error::DoesntExist { symbol, .. } => symbol.as_str(),
+                    other => panic!("Expected DoesntExist, got {:?}", other),
+                })
+                .collect();
+            assert_eq!(symbols, ["dl_api_missing_strlen", "dl_api_missing"]);
+        }
+        other => panic!("Expected NoneExist, got {:?}", other.err()),
+    }
+}
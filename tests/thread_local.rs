@@ -0,0 +1,45 @@
+// DL API
+//
+// Copyright (c) 2018-2020 Jeron Aldaron Lau
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0>, or the Zlib License, <LICENSE-ZLIB
+// or http://opensource.org/licenses/Zlib>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// glibc exports `errno` as a thread-local variable (for its own libraries).
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+extern "C" {
+    fn __errno_location() -> *mut i32;
+}
+
+dl_api::linker!(extern "C" Libc "libc.so.6" {
+    thread_local static errno: i32;
+});
+
+#[test]
+fn thread_local_has_an_address_per_thread() {
+    let libc = Libc::new().unwrap();
+    let main = libc.errno.get();
+    assert_eq!(main, unsafe { __errno_location() });
+    assert_eq!(libc.errno.get(), main);
+
+    let addresses = std::thread::scope(|scope| {
+        let thread = scope.spawn(|| {
+            let address = libc.errno.get();
+            assert_eq!(address, unsafe { __errno_location() });
+            assert_eq!(libc.errno.get(), address);
+            address as usize
+        });
+        thread.join().unwrap()
+    });
+    assert_ne!(addresses, main as usize);
+
+    // A new variable on the same thread is looked up again.
+    drop(libc);
+    let libc = Libc::new().unwrap();
+    assert_eq!(libc.errno.get(), main);
+    unsafe { *libc.errno.get() = 42 };
+    assert_eq!(unsafe { *__errno_location() }, 42);
+}